@@ -0,0 +1,120 @@
+//! Bounces random triangles around the screen, writing the frames to `images/test_frame_XXX.bmp`
+
+use rand::{rng, rngs::ThreadRng, Rng};
+use std::iter::zip;
+
+use software_rasterizer::Result;
+use software_rasterizer::rendering::bitmap::{write_bmp_file, RowOrder};
+use software_rasterizer::rendering::RenderTarget;
+use software_rasterizer::rendering::pipeline::{self, CullMode, RenderSettings};
+use software_rasterizer::vector_math::vector::*;
+
+const WIDTH: usize = 512;
+const HEIGHT: usize = 512;
+
+struct Scene {
+    vertices: Vec<Float2>,
+    vertex_velocities: Vec<Float2>,
+    triangle_colors: Vec<Float3>
+}
+
+fn main() {
+    if let Err(why) = run() {
+        eprintln!("Error: {}", why);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
+    let mut scene = create_test_images();
+    let mut render_target = RenderTarget::new(WIDTH, HEIGHT);
+    // The random triangles have arbitrary winding, so draw both sides
+    let settings = RenderSettings { cull_mode: CullMode::None, ..RenderSettings::default() };
+
+    for i in 0..5 {
+        pipeline::render2d(&scene.vertices, &scene.triangle_colors, &mut render_target, &settings)?;
+    
+        // Save the current stage of the image buffer to a bitmap
+        let file_name = format!("images/test_frame_{:03}.bmp", i);
+        write_bmp_file(&render_target.image_buffer, None, RowOrder::default(), file_name)?;
+
+        update(&mut scene.vertices, &mut scene.vertex_velocities, 0.25);
+        render_target.clear();
+    }
+    Ok(())
+}
+
+fn update(vertices: &mut Vec<Float2>, velocities: &mut Vec<Float2>, delta_t: f64) {
+    for (vert, vel) in zip(vertices, velocities) {
+        *vert += *vel * delta_t;
+        // Flip the velocities, if the points end up outside the render box
+        if vert.x < 0f64 || vert.x > WIDTH as f64{
+            vel.x *= -1f64;
+        }
+        if vert.y < 0f64 || vert.y > HEIGHT as f64 {
+            vel.y *= -1f64;
+        }
+    }
+}
+
+/// Generate randomly initialized triangles
+fn create_test_images() -> Scene {
+    // Get the random vertices, triangle velocities and colors
+    let (points, velocities, triangle_colors) = setup_triangles(WIDTH, HEIGHT);
+
+    Scene { vertices: points, vertex_velocities: velocities, triangle_colors }
+}
+
+/// Initialize triangles with random positions, velocities and colors. Returns the flattened vertices vector, the velocities and the triangle colors.
+fn setup_triangles(screen_width: usize, screen_height: usize) -> (Vec<Float2>, Vec<Float2>, Vec<Float3>) {
+    const TRIANGLE_COUNT: usize = 50;
+
+    // Initialize data arrays with 0-vectors
+    let mut points = vec![Float2::zeros(); TRIANGLE_COUNT * 3];
+    let mut velocities = vec![Float2::zeros(); points.len()];
+    let mut triangle_colors = vec![Float3::zeros(); TRIANGLE_COUNT];
+
+    // Compute the image center
+    let center = Float2::new((screen_width / 2) as f64, (screen_height / 2) as f64);
+
+    // Initialize a randomizer
+    let mut g = rng();
+
+    // Generate the random positions of each vertex in the x/y range 0.35..0.65 (widths/heights)
+    for p in points.iter_mut() {
+        let random_point = random_float2(&mut g, screen_width, screen_height);
+        let offset = (random_point - center) * 0.3;
+        *p = center + offset;
+    }
+
+    // Generate random velocities for each triangle
+    for i in (0..velocities.len()).step_by(3) {
+        let random_velocity = random_float2(&mut g, screen_width, screen_height);
+        let velocity = (random_velocity - center) * 0.5;
+        velocities[i] = velocity;
+        velocities[i + 1] = velocity;
+        velocities[i + 2] = velocity;
+    }
+
+    // Assign a random color to each triangle
+    for c in triangle_colors.iter_mut() {
+        *c = random_color(&mut g);
+    }
+
+    // Return the vertices, velocities and colors
+    (points, velocities, triangle_colors)
+}
+
+/// Generate a random Float2 with x/y ranges `0..width` and `0..height`.
+fn random_float2(rng: &mut ThreadRng, width: usize, height: usize) -> Float2 {
+    Float2::new(rng.random_range(0..width) as f64, rng.random_range(0..height) as f64)
+}
+
+/// Generate a random RGB color.
+fn random_color(rng: &mut ThreadRng) -> Float3 {
+    Float3::new(
+        rng.random_range(0.0..1.0),
+        rng.random_range(0.0..1.0),
+        rng.random_range(0.0..1.0)
+    )
+}
@@ -0,0 +1,51 @@
+//! Renders a turntable animation of Suzanne, writing the frames to `images/monkey_frame_XXX.bmp`
+
+use software_rasterizer::Result;
+use software_rasterizer::formats::load_model;
+use software_rasterizer::objects::Model;
+use software_rasterizer::rendering::bitmap::{write_bmp_file, RowOrder};
+use software_rasterizer::rendering::RenderTarget;
+use software_rasterizer::rendering::lighting::{Light, LightingShader, ShadingMode};
+use software_rasterizer::rendering::pipeline::{self, RenderSettings};
+use software_rasterizer::rendering::transforms::{Camera, rotation_from_angles};
+use software_rasterizer::vector_math::vector::Float3;
+
+const WIDTH: usize = 512;
+const HEIGHT: usize = 512;
+
+fn main() {
+    if let Err(why) = run() {
+        eprintln!("Error: {}", why);
+        std::process::exit(1);
+    }
+}
+
+/// Render a turntable animation of Suzanne
+fn run() -> Result<()> {
+    let mut model = load_suzanne_model()?;
+    let mut render_target = RenderTarget::new(WIDTH, HEIGHT);
+    let camera = Camera::new(Float3::zeros(), 60.0);
+    let settings = RenderSettings::default();
+    let light = Light::Directional { direction: Float3::new(-1.0, -1.0, 1.0), color: Float3::splat(1.0), intensity: 1.0 };
+    let shader = LightingShader::new(vec![light], ShadingMode::Phong, camera.position);
+
+    for i in 0..20 {
+        pipeline::render3d(&model, &shader, &shader, &mut render_target, &camera, &settings)?;
+    
+        // Save the current stage of the image buffer to a bitmap
+        let file_name = format!("images/monkey_frame_{:03}.bmp", i);
+        write_bmp_file(&render_target.image_buffer, None, RowOrder::default(), file_name)?;
+
+        let frame = (i + 1) as f64;
+        model.transform.rotation = rotation_from_angles(0.02 * frame, 0.1 * frame, 0.0);
+        render_target.clear();
+    }
+    Ok(())
+}
+
+fn load_suzanne_model() -> Result<Model> {
+    let mut model = load_model("models/suzanne.obj")?;
+    model.transform.position += Float3::new(0.0, 0.0, 3.0);
+    model.triangle_colors.fill(Float3::new(0.8, 0.55, 0.3));
+    Ok(model)
+}
@@ -0,0 +1,337 @@
+use std::path::PathBuf;
+
+use software_rasterizer::rendering::bitmap::RowOrder;
+use software_rasterizer::rendering::image_format::ImageFormat;
+use software_rasterizer::rendering::lighting::ShadingMode;
+use software_rasterizer::rendering::multisampling::SampleCount;
+use software_rasterizer::rendering::pipeline::Precision;
+use software_rasterizer::rendering::png::BitDepth;
+use software_rasterizer::rendering::resampling::ResamplingFilter;
+use software_rasterizer::vector_math::vector::{Float2, Float3};
+
+pub const USAGE: &str = "\
+Usage: software_rasterizer [OPTIONS] <INPUT>
+
+Renders the OBJ file INPUT to one image per frame.
+
+Options:
+  -o, --output <PATH>          Output path. A run of '#' is replaced by the zero-padded frame number,
+                               which is required for more than one frame [default: render_###.bmp]
+  -f, --format <FORMAT>        Output format: bmp, png, hdr or pfm [default: from the output extension]
+      --bit-depth <BITS>       Bits per channel of PNG files: 8 or 16 [default: 8]
+      --row-order <ORDER>      Row order of BMP files: bottom-up or top-down [default: bottom-up]
+      --alpha                  Store the coverage as alpha channel, leaving the background transparent (BMP and
+                               PNG only)
+      --depth <PATH>           Also write the exact depth buffer as PFM file with 64-bit samples, with '#' replaced
+                               like in the output path
+  -s, --size <WxH>             Image size in pixels [default: 512x512]
+      --camera <X,Y,Z>         Camera position [default: 0,0,-3]
+      --target <X,Y,Z>         Point the camera looks at [default: 0,0,0]
+      --fov <DEGREES>          Vertical field of view [default: 60]
+  -n, --frames <COUNT>         Number of frames [default: 1]
+  -r, --rotation <YAW[,PITCH]> Rotation of the model per frame in degrees [default: 0,0]
+      --shading <MODE>         Shading mode: flat, gouraud or phong [default: phong]
+      --background <R,G,B>     Background color with channels in 0..1 [default: 0,0,0]
+      --msaa <SAMPLES>         Samples per pixel for multisample anti-aliasing: 1, 2, 4, 8 or 16 [default: 1]
+      --supersample <FACTOR>   Render at FACTOR times the size and downsample, from 1 to 16 [default: 1]
+      --filter <FILTER>        Downsampling filter: box, tent, mitchell or lanczos [default: box]
+  -j, --threads <COUNT>        Number of rendering threads, 0 for one per core [default: 0]
+      --precision <MODE>       Rasterization precision: float, or fixed for bit-exact coverage on every
+                               platform [default: float]
+  -h, --help                   Print this help";
+
+/// Settings of a batch render
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub input: PathBuf,
+    /// Output path pattern, where a run of '#' stands for the frame number
+    pub output: String,
+    pub format: ImageFormat,
+    /// Whether to write the coverage as alpha channel
+    pub alpha: bool,
+    /// Path pattern of the depth buffer files like `output`, if the depth should be written
+    pub depth_output: Option<String>,
+    pub width: usize,
+    pub height: usize,
+    pub camera_position: Float3,
+    pub camera_target: Float3,
+    /// Vertical field of view in degrees
+    pub fov: f64,
+    pub frames: usize,
+    /// Rotation of the model around the y-axis (x) and the x-axis (y) per frame in degrees
+    pub rotation: Float2,
+    pub shading_mode: ShadingMode,
+    pub background: Float3,
+    pub sample_count: SampleCount,
+    /// Factor of the rendered size over the output size along each axis
+    pub supersampling: usize,
+    pub filter: ResamplingFilter,
+    /// Number of rendering threads, where 0 means one per core
+    pub threads: usize,
+    pub precision: Precision,
+}
+
+impl Options {
+    /// The output path of the frame with the given index
+    pub fn get_output_path(&self, frame: usize) -> PathBuf {
+        frame_path(&self.output, frame)
+    }
+
+    /// The path of the depth buffer file of the frame with the given index
+    pub fn get_depth_output_path(&self, frame: usize) -> Option<PathBuf> {
+        self.depth_output.as_ref().map(|pattern| frame_path(pattern, frame))
+    }
+}
+
+/// Replace the first run of '#' in the pattern by the zero-padded frame number
+fn frame_path(pattern: &str, frame: usize) -> PathBuf {
+    let Some(start) = pattern.find('#') else {
+        return PathBuf::from(pattern);
+    };
+    let digits = pattern[start..].chars().take_while(|&c| c == '#').count();
+    let number = format!("{:0width$}", frame, width = digits);
+    PathBuf::from(format!("{}{}{}", &pattern[..start], number, &pattern[start + digits..]))
+}
+
+/// What the command line asks for
+pub enum Command {
+    Render(Box<Options>),
+    Help,
+}
+
+/// Parse the command line arguments, excluding the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut input = None;
+    let mut output = String::from("render_###.bmp");
+    let mut format = None;
+    let mut bit_depth = BitDepth::Eight;
+    let mut row_order = RowOrder::BottomUp;
+    let mut alpha = false;
+    let mut depth_output = None;
+    let (mut width, mut height) = (512, 512);
+    let mut camera_position = Float3::new(0.0, 0.0, -3.0);
+    let mut camera_target = Float3::zeros();
+    let mut fov = 60.0;
+    let mut frames = 1;
+    let mut rotation = Float2::zeros();
+    let mut shading_mode = ShadingMode::Phong;
+    let mut background = Float3::zeros();
+    let mut sample_count = SampleCount::One;
+    let mut supersampling = 1;
+    let mut filter = ResamplingFilter::Box;
+    let mut threads = 0;
+    let mut precision = Precision::Float;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            if input.replace(PathBuf::from(&arg)).is_some() {
+                return Err(format!("Unexpected argument '{}', only one input file is supported", arg));
+            }
+            continue;
+        }
+
+        // Options take their value either as the next argument or after an '='
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--alpha" => {
+                alpha = true;
+                continue;
+            }
+            _ => (),
+        }
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("The option '{}' needs a value", name)),
+        };
+        let invalid = |expected: &str| format!("Invalid value '{}' for '{}', expected {}", value, name, expected);
+
+        match name.as_str() {
+            "-o" | "--output" => output = value.clone(),
+            "--depth" => depth_output = Some(value.clone()),
+            "-f" | "--format" => format = Some(ImageFormat::from_name(&value).ok_or_else(|| invalid("bmp, png, hdr or pfm"))?),
+            "--bit-depth" => {
+                bit_depth = match value.as_str() {
+                    "8" => BitDepth::Eight,
+                    "16" => BitDepth::Sixteen,
+                    _ => return Err(invalid("8 or 16")),
+                };
+            }
+            "--row-order" => {
+                row_order = match value.to_ascii_lowercase().as_str() {
+                    "bottom-up" => RowOrder::BottomUp,
+                    "top-down" => RowOrder::TopDown,
+                    _ => return Err(invalid("bottom-up or top-down")),
+                };
+            }
+            "-s" | "--size" => {
+                (width, height) = value.split_once('x')
+                                       .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                                       .filter(|&(w, h)| w > 0 && h > 0)
+                                       .ok_or_else(|| invalid("a size like 800x600"))?;
+            }
+            "--camera" => camera_position = parse_float3(&value).ok_or_else(|| invalid("a position like 0,0,-3"))?,
+            "--target" => camera_target = parse_float3(&value).ok_or_else(|| invalid("a position like 0,0,0"))?,
+            "--fov" => {
+                fov = value.parse().ok()
+                           .filter(|&fov| fov > 0.0 && fov < 180.0)
+                           .ok_or_else(|| invalid("an angle between 0 and 180 degrees"))?;
+            }
+            "-n" | "--frames" => frames = value.parse().ok().filter(|&n| n > 0).ok_or_else(|| invalid("a positive number"))?,
+            "-r" | "--rotation" => {
+                let angles = parse_floats(&value).ok_or_else(|| invalid("angles like 5 or 5,1"))?;
+                rotation = match angles[..] {
+                    [yaw] => Float2::new(yaw, 0.0),
+                    [yaw, pitch] => Float2::new(yaw, pitch),
+                    _ => return Err(invalid("angles like 5 or 5,1")),
+                };
+            }
+            "--shading" => shading_mode = ShadingMode::from_name(&value).ok_or_else(|| invalid("flat, gouraud or phong"))?,
+            "--background" => background = parse_float3(&value).ok_or_else(|| invalid("a color like 0.1,0.1,0.1"))?,
+            "--msaa" => {
+                sample_count = value.parse().ok().and_then(SampleCount::from_count).ok_or_else(|| invalid("1, 2, 4, 8 or 16"))?;
+            }
+            "--supersample" => {
+                supersampling = value.parse().ok().filter(|factor| (1..=16).contains(factor)).ok_or_else(|| invalid("a factor from 1 to 16"))?;
+            }
+            "--filter" => filter = ResamplingFilter::from_name(&value).ok_or_else(|| invalid("box, tent, mitchell or lanczos"))?,
+            "-j" | "--threads" => threads = value.parse().map_err(|_| invalid("a number of threads"))?,
+            "--precision" => {
+                precision = match value.to_ascii_lowercase().as_str() {
+                    "float" => Precision::Float,
+                    "fixed" => Precision::FixedPoint,
+                    _ => return Err(invalid("float or fixed")),
+                };
+            }
+            _ => return Err(format!("Unknown option '{}'", name)),
+        }
+    }
+
+    let Some(input) = input else {
+        return Err("Missing the input file".to_string());
+    };
+    if camera_position == camera_target {
+        return Err("The camera can't look at its own position, '--camera' and '--target' must differ".to_string());
+    }
+    for path in std::iter::once(&output).chain(&depth_output) {
+        if frames > 1 && !path.contains('#') {
+            return Err(format!("The output path '{}' needs a '#' for the frame number when rendering {} frames", path, frames));
+        }
+    }
+    let format = match format.or_else(|| ImageFormat::from_path(&output)) {
+        Some(ImageFormat::Bmp { .. }) => ImageFormat::Bmp { row_order },
+        Some(ImageFormat::Png { .. }) => ImageFormat::Png { bit_depth },
+        Some(format) => format,
+        None => return Err(format!("Unknown format of the output path '{}', use '--format'", output)),
+    };
+    if alpha && !format.supports_alpha() {
+        return Err("The output format can't store an alpha channel".to_string());
+    }
+
+    Ok(Command::Render(Box::new(Options {
+        input,
+        output,
+        format,
+        alpha,
+        depth_output,
+        width,
+        height,
+        camera_position,
+        camera_target,
+        fov,
+        frames,
+        rotation,
+        shading_mode,
+        background,
+        sample_count,
+        supersampling,
+        filter,
+        threads,
+        precision,
+    })))
+}
+
+/// Parse comma-separated floats
+fn parse_floats(value: &str) -> Option<Vec<f64>> {
+    value.split(',').map(|s| s.trim().parse().ok()).collect()
+}
+
+/// Parse three comma-separated floats
+fn parse_float3(value: &str) -> Option<Float3> {
+    match parse_floats(value)?[..] {
+        [x, y, z] => Some(Float3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse the whitespace-separated arguments into render options
+    fn parse(args: &str) -> Result<Options, String> {
+        match parse_args(args.split_whitespace().map(String::from))? {
+            Command::Render(options) => Ok(*options),
+            Command::Help => Err("help".to_string()),
+        }
+    }
+
+    #[test]
+    fn options_take_their_value_after_an_equals_sign_or_as_the_next_argument() {
+        let options = parse("model.obj --size=800x600 -o=frames/#.png --camera 1,2,3 --target=0,1,0 -n=2").unwrap();
+        assert_eq!((options.width, options.height), (800, 600));
+        assert_eq!(options.get_output_path(1), PathBuf::from("frames/1.png"));
+        assert_eq!(options.format, ImageFormat::Png { bit_depth: BitDepth::Eight });
+        assert_eq!((options.camera_position, options.camera_target), (Float3::new(1.0, 2.0, 3.0), Float3::new(0.0, 1.0, 0.0)));
+        assert_eq!(options.frames, 2);
+        assert!(matches!(parse_args(["-h".to_string()]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn rejects_missing_values_and_unknown_options() {
+        assert_eq!(parse("model.obj --fov").unwrap_err(), "The option '--fov' needs a value");
+        let error = parse("model.obj --fov=").unwrap_err();
+        assert_eq!(error, "Invalid value '' for '--fov', expected an angle between 0 and 180 degrees");
+        assert_eq!(parse("model.obj --fast=yes").unwrap_err(), "Unknown option '--fast'");
+        assert_eq!(parse("--size 8x8").unwrap_err(), "Missing the input file");
+    }
+
+    #[test]
+    fn validates_the_size() {
+        assert_eq!(parse("model.obj -s 1x2048").map(|options| (options.width, options.height)), Ok((1, 2048)));
+        for size in ["0x10", "10x0", "10", "10x", "x10", "-1x10", "10x10x10", "axb", "1.5x2"] {
+            let error = parse(&format!("model.obj --size {}", size)).unwrap_err();
+            assert_eq!(error, format!("Invalid value '{}' for '--size', expected a size like 800x600", size));
+        }
+    }
+
+    #[test]
+    fn multiple_frames_need_a_frame_number_in_every_output_path() {
+        assert!(parse("model.obj -n 3").is_ok());
+        assert!(parse("model.obj -n 3 -o out_##.bmp --depth depth_##.pfm").is_ok());
+        assert!(parse("model.obj -n 1 -o out.bmp --depth depth.pfm").is_ok());
+        assert!(parse("model.obj -n 3 -o out.bmp").unwrap_err().contains("'out.bmp' needs a '#'"));
+        assert!(parse("model.obj -n 3 -o out_#.bmp --depth depth.pfm").unwrap_err().contains("'depth.pfm' needs a '#'"));
+    }
+
+    #[test]
+    fn alpha_needs_a_format_with_an_alpha_channel() {
+        assert!(parse("model.obj --alpha -o out.png").unwrap().alpha);
+        assert!(parse("model.obj --alpha -o out.bmp").unwrap().alpha);
+        for output in ["out.hdr", "out.pfm"] {
+            let error = parse(&format!("model.obj --alpha -o {}", output)).unwrap_err();
+            assert_eq!(error, "The output format can't store an alpha channel");
+        }
+        assert!(parse("model.obj --alpha -o out.png -f hdr").is_err());
+    }
+
+    #[test]
+    fn the_camera_must_not_look_at_its_own_position() {
+        assert!(parse("model.obj --camera 1,2,3 --target 1,2,3").unwrap_err().contains("must differ"));
+        assert!(parse("model.obj --camera 0,0,0").unwrap_err().contains("must differ"));
+    }
+}
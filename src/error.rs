@@ -0,0 +1,81 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors returned by the loaders, writers and renderers of the crate
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// A text-based file could not be parsed
+    Parse {
+        /// 1-based line number
+        line: usize,
+        /// 1-based column of the offending token within the line
+        column: usize,
+        message: String,
+    },
+    /// An error that occurred while handling the file at `path`
+    File {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+    /// The image or render target has zero width or height
+    EmptyImage,
+    /// The image data is inconsistent or malformed, e.g. an alpha plane has a different size than the image
+    InvalidImage(String),
+    /// The image file uses a format or feature the readers do not support
+    UnsupportedImage(String),
+    /// The image dimensions exceed what the file format can store
+    ImageTooLarge {
+        width: usize,
+        height: usize,
+    },
+    /// The buffers of the model do not fit together, e.g. the number of normals differs from the number of vertices
+    InvalidModel(String),
+}
+
+impl Error {
+    /// Creates a parse error at the given 1-based line and column
+    pub fn parse(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Error::Parse { line, column, message: message.into() }
+    }
+
+    /// Attach the path of the file the error occurred in
+    pub fn in_file(self, path: impl Into<PathBuf>) -> Self {
+        Error::File { path: path.into(), source: Box::new(self) }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Parse { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message),
+            Error::File { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::EmptyImage => write!(f, "the image has zero width or height"),
+            Error::InvalidImage(message) => write!(f, "invalid image: {}", message),
+            Error::UnsupportedImage(message) => write!(f, "unsupported image: {}", message),
+            Error::ImageTooLarge { width, height } => write!(f, "the image size {}x{} is too large", width, height),
+            Error::InvalidModel(message) => write!(f, "invalid model: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::File { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -1 +1,180 @@
-pub mod obj_format;
\ No newline at end of file
+pub mod mtl_format;
+pub mod obj_format;
+
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::objects::Model;
+use crate::rendering::image_format::read_image_file;
+use crate::rendering::texture::Texture;
+
+/// Load an OBJ file together with its material libraries as a model.
+///
+/// The material libraries are resolved relative to the OBJ file. Missing libraries are skipped, so the affected
+/// triangles have no material. The diffuse textures of the materials are loaded with mipmaps, skipping missing
+/// files and formats the image readers do not support. If the file has no normals, they are computed from the faces
+/// and smoothing groups.
+pub fn load_model(obj_path: impl AsRef<Path>) -> Result<Model> {
+    let obj_path = obj_path.as_ref();
+    let obj_str = read_to_string(obj_path).map_err(|why| Error::from(why).in_file(obj_path))?;
+    let mesh = obj_format::load_obj_file(&obj_str).map_err(|why| why.in_file(obj_path))?;
+
+    let directory = obj_path.parent().unwrap_or(Path::new(""));
+    let mut materials = Vec::new();
+    for library in mesh.material_libraries.iter() {
+        let mtl_path = directory.join(library);
+        let mtl_str = match read_to_string(&mtl_path) {
+            Ok(mtl_str) => mtl_str,
+            Err(why) if why.kind() == io::ErrorKind::NotFound => continue,
+            Err(why) => return Err(Error::from(why).in_file(mtl_path)),
+        };
+        let mtl_directory = mtl_path.parent().unwrap_or(directory);
+        materials.extend(mtl_format::load_mtl_file(&mtl_str, mtl_directory).map_err(|why| why.in_file(&mtl_path))?);
+    }
+
+    // Materials often share a texture, so each file is only loaded once
+    let mut textures: HashMap<PathBuf, Option<Arc<Texture>>> = HashMap::new();
+    for material in materials.iter_mut() {
+        let Some(path) = &material.diffuse_map else {
+            continue;
+        };
+        let texture = match textures.get(path) {
+            Some(texture) => texture.clone(),
+            None => {
+                let texture = load_texture(path)?.map(Arc::new);
+                textures.insert(path.clone(), texture.clone());
+                texture
+            }
+        };
+        material.diffuse_texture = texture;
+    }
+
+    let mut model = mesh.to_model(&materials);
+    if model.normals.is_empty() {
+        model.compute_normals();
+    }
+    Ok(model)
+}
+
+/// Load the image file as texture with mipmaps, or `None` if the file is missing or its format is not supported
+fn load_texture(path: &Path) -> Result<Option<Texture>> {
+    match read_image_file(path) {
+        Ok(decoded) => Ok(Some(Texture::with_mipmaps(decoded.image))),
+        Err(Error::File { source, path }) => match *source {
+            Error::UnsupportedImage(_) => Ok(None),
+            Error::Io(why) if why.kind() == io::ErrorKind::NotFound => Ok(None),
+            source => Err(source.in_file(path)),
+        },
+        Err(why) => Err(why),
+    }
+}
+
+/// A whitespace-separated word of a line
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    text: &'a str,
+    /// 1-based column of the first character
+    column: usize,
+}
+
+/// An error message about the token at a 1-based column, which becomes a parse error once the line is known
+type TokenError = (usize, String);
+
+/// Iterate over the lines with their 1-based line numbers, stripping `#` comments and joining lines ending in a
+/// backslash with the following line. The line number is the one of the first joined line, and columns of
+/// continued lines count from the start of that line.
+fn logical_lines(source: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut lines = source.lines().enumerate();
+    std::iter::from_fn(move || {
+        let (index, first) = lines.next()?;
+        let mut line = String::from(first);
+        while line.ends_with('\\') {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next),
+                None => break,
+            }
+        }
+        if let Some(comment) = line.find('#') {
+            line.truncate(comment);
+        }
+        Some((index + 1, line))
+    })
+}
+
+/// Split a line into its whitespace-separated tokens
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    line.split_whitespace()
+        .map(|text| {
+            // The tokens are slices of the line, so their offset follows from the pointers
+            let offset = text.as_ptr() as usize - line.as_ptr() as usize;
+            Token { text, column: line[..offset].chars().count() + 1 }
+        })
+        .collect()
+}
+
+/// Join the tokens with single spaces, e.g. for names containing spaces
+fn join(tokens: &[Token]) -> String {
+    tokens.iter().map(|t| t.text).collect::<Vec<_>>().join(" ")
+}
+
+/// Parse every argument as a float
+fn parse_floats(arguments: &[Token]) -> Result<Vec<f64>, TokenError> {
+    arguments.iter()
+             .map(|a| a.text.parse::<f64>().map_err(|_| (a.column, format!("Invalid number \"{}\"", a.text))))
+             .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+    use crate::vector_math::vector::Float3;
+
+    #[test]
+    fn loads_diffuse_textures_once_and_skips_missing_ones() {
+        let directory = std::env::temp_dir().join(format!("software_rasterizer_textures_{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        write(directory.join("red.ppm"), b"P3 1 1 255 255 0 0").unwrap();
+        write(directory.join("scene.mtl"), "newmtl a\nmap_Kd red.ppm\nnewmtl b\nmap_Kd -s 2 2 red.ppm\n\
+                                            newmtl c\nmap_Kd missing.png\nmap_Bump missing.png\n").unwrap();
+        write(directory.join("scene.obj"), "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                                            usemtl a\nf 1 2 3\nusemtl b\nf 1 2 3\nusemtl c\nf 1 2 3\n").unwrap();
+        let model = load_model(directory.join("scene.obj"));
+        remove_dir_all(&directory).unwrap();
+
+        let model = model.unwrap();
+        let textures: Vec<_> = model.materials.iter().map(|material| material.diffuse_texture.clone()).collect();
+        let [Some(a), Some(b), None] = &textures[..] else {
+            panic!("expected the first two materials to have a texture");
+        };
+        assert!(Arc::ptr_eq(a, b));
+        assert_eq!(a.get_image()[[0, 0]], Float3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn resolves_specular_and_bump_maps_next_to_the_material_library() {
+        let directory = std::env::temp_dir().join(format!("software_rasterizer_maps_{}", std::process::id()));
+        create_dir_all(directory.join("materials")).unwrap();
+        write(directory.join("materials/scene.mtl"), "newmtl a\nmap_Ks -clamp on shiny.png\nmap_Bump -bm 0.5 bumpy map.png\n\
+                                                      newmtl b\nbump flat.png\n").unwrap();
+        write(directory.join("scene.obj"), "mtllib materials/scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                                            usemtl a\nf 1 2 3\nusemtl b\nf 1 2 3\n").unwrap();
+        let model = load_model(directory.join("scene.obj"));
+        remove_dir_all(&directory).unwrap();
+
+        let model = model.unwrap();
+        let [a, b] = &model.materials[..] else {
+            panic!("expected two materials, found {}", model.materials.len());
+        };
+        assert_eq!(a.specular_map, Some(directory.join("materials/shiny.png")));
+        assert_eq!(a.bump_map, Some(directory.join("materials/bumpy map.png")));
+        assert_eq!((&b.specular_map, &b.bump_map), (&None, &Some(directory.join("materials/flat.png"))));
+        assert!(a.diffuse_map.is_none() && a.diffuse_texture.is_none());
+    }
+}
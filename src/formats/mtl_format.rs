@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::formats::{join, logical_lines, parse_floats, tokenize, Token, TokenError};
+use crate::objects::Material;
+use crate::vector_math::vector::Float3;
+
+/// Parse the contents of an MTL file into its materials.
+///
+/// Texture paths are resolved relative to `directory`, which should be the directory containing the MTL file.
+/// Statements that are not supported, e.g. for reflection maps, are ignored.
+pub fn load_mtl_file(mtl_str: &str, directory: &Path) -> Result<Vec<Material>> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (line_number, line) in logical_lines(mtl_str) {
+        let tokens = tokenize(&line);
+        let Some((keyword, arguments)) = tokens.split_first() else {
+            continue;
+        };
+        let error = |(column, message): TokenError| Error::parse(line_number, column, message);
+        let statement_error = |message: String| Error::parse(line_number, keyword.column, message);
+
+        if keyword.text == "newmtl" {
+            if arguments.is_empty() {
+                return Err(statement_error("A material needs a name".to_string()));
+            }
+            materials.push(Material::new(&join(arguments)));
+            continue;
+        }
+
+        // Every other statement describes the current material
+        let is_supported = matches!(keyword.text, "Ka" | "Kd" | "Ks" | "Ns" | "d" | "Tr" | "illum" | "map_Kd" | "map_Ks"
+                                                  | "map_Bump" | "bump");
+        if !is_supported {
+            continue;
+        }
+        let Some(material) = materials.last_mut() else {
+            return Err(statement_error(format!("\"{}\" appears before the first material", keyword.text)));
+        };
+
+        match keyword.text {
+            "Ka" => material.ambient = parse_color(keyword, arguments).map_err(error)?,
+            "Kd" => material.diffuse = parse_color(keyword, arguments).map_err(error)?,
+            "Ks" => material.specular = parse_color(keyword, arguments).map_err(error)?,
+            "Ns" => material.shininess = parse_float(keyword, arguments).map_err(error)?,
+            "d" => material.opacity = parse_float(keyword, arguments).map_err(error)?,
+            "Tr" => material.opacity = 1.0 - parse_float(keyword, arguments).map_err(error)?,
+            "illum" => {
+                let [argument] = arguments else {
+                    return Err(statement_error(format!("Expected a single value, found {}", arguments.len())));
+                };
+                material.illumination_model = argument.text.parse().map_err(|_| {
+                    error((argument.column, format!("Invalid illumination model \"{}\"", argument.text)))
+                })?;
+            }
+            "map_Kd" => material.diffuse_map = Some(parse_map(keyword, arguments, directory).map_err(error)?),
+            "map_Ks" => material.specular_map = Some(parse_map(keyword, arguments, directory).map_err(error)?),
+            _ => material.bump_map = Some(parse_map(keyword, arguments, directory).map_err(error)?),
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Parse the single float following the keyword
+fn parse_float(keyword: &Token, arguments: &[Token]) -> Result<f64, TokenError> {
+    match parse_floats(arguments)?[..] {
+        [value] => Ok(value),
+        _ => Err((keyword.column, format!("Expected a single value, found {}", arguments.len()))),
+    }
+}
+
+/// Parse the RGB color following the keyword. A single value is used for all three channels.
+fn parse_color(keyword: &Token, arguments: &[Token]) -> Result<Float3, TokenError> {
+    if let Some(first) = arguments.first().filter(|a| matches!(a.text, "spectral" | "xyz")) {
+        return Err((first.column, format!("Colors given as \"{}\" are not supported", first.text)));
+    }
+    match parse_floats(arguments)?[..] {
+        [value] => Ok(Float3::splat(value)),
+        [r, g, b] => Ok(Float3::new(r, g, b)),
+        _ => Err((keyword.column, format!("Expected 1 or 3 values for a color, found {}", arguments.len()))),
+    }
+}
+
+/// Parse the file name of a texture map following the keyword, skipping the options in front of it
+fn parse_map(keyword: &Token, arguments: &[Token], directory: &Path) -> Result<PathBuf, TokenError> {
+    let mut i = 0;
+    while i < arguments.len() && arguments[i].text.starts_with('-') {
+        let option = arguments[i];
+        i += 1;
+        match option.text {
+            // Options with a single argument
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => i += 1,
+            "-mm" => i += 2,
+            // Offset, scale and turbulence take one to three numbers
+            "-o" | "-s" | "-t" => {
+                let count = arguments[i..].iter().take(3).take_while(|a| a.text.parse::<f64>().is_ok()).count();
+                i += count;
+            }
+            _ => return Err((option.column, format!("Unknown texture map option \"{}\"", option.text))),
+        }
+    }
+
+    if i >= arguments.len() {
+        return Err((keyword.column, "The texture map is missing a file name".to_string()));
+    }
+    // File names may contain spaces
+    Ok(directory.join(join(&arguments[i..])))
+}
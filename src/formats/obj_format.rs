@@ -1,39 +1,301 @@
-use crate::vector_math::vector::Float3;
-
-pub fn load_obj_file(obj_str: String) -> Vec<Float3> {
-
-    let mut vertices: Vec<Float3> = Vec::new();
-    let mut triangle_vertices: Vec<Float3> = Vec::new();
-
-    // Loop over lines in the file
-    for line in obj_str.lines() {
-        // Handle a line for a vertex
-        if let Some(trimmed) = line.strip_prefix("v ") {
-            let v: Vec<f64> = trimmed.split(' ')
-                                     .map(|s| s.parse::<f64>()
-                                                     .expect("Failed parsing a vertex line!"))
-                                     .collect();
-            // Add the vertex as a Float3 - fails if the line is missing data!
-            vertices.push(Float3 { x: v[0], y: v[1], z: v[2]});
-        // Handle the triplets for faces. Each triplet is face_idx/texture_idx/normal_idx
-        } else if let Some(trimmed) = line.strip_prefix("f ") {
-            let triplets: Vec<Vec<usize>> = trimmed.split(' ')
-                                                    .map(|s| s.split('/')
-                                                                    .map(|t| t.parse::<usize>()
-                                                                    .expect("Failed parsing a triplet line!"))
-                                                    .collect::<Vec<usize>>()).collect();
-
-            for idx in 0..triplets.len() {
-                // Extract the face index
-                let point_idx = triplets[idx][0] - 1;
+use crate::error::{Error, Result};
+use crate::formats::{join, logical_lines, parse_floats, tokenize, TokenError};
+use crate::objects::{Material, Model};
+use crate::rendering::transforms::Transform;
+use crate::vector_math::vector::{Float2, Float3};
+
+/// A corner of an OBJ face, given by 0-based indices into the attribute lists of the mesh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+/// A triangle of an OBJ mesh. Polygons with more than three vertices are split into triangle fans.
+#[derive(Clone, Copy, Debug)]
+pub struct ObjTriangle {
+    pub vertices: [ObjVertex; 3],
+    /// Index into `ObjMesh::objects`
+    pub object: usize,
+    /// Index into `ObjMesh::groups`
+    pub groups: usize,
+    /// Index into `ObjMesh::materials`, if a material is in use
+    pub material: Option<usize>,
+    /// Smoothing group of the triangle. 0 means smoothing is off, and `None` that no `s` statement came before the
+    /// triangle.
+    pub smoothing_group: Option<u32>,
+}
+
+/// An indexed triangle mesh as described by a Wavefront OBJ file
+#[derive(Clone, Debug, Default)]
+pub struct ObjMesh {
+    /// Vertex positions. The optional weight `w` only affects rational curves and surfaces, so it is dropped.
+    pub positions: Vec<Float3>,
+    /// Vertex colors, one per position. Empty if no vertex has a color.
+    pub colors: Vec<Float3>,
+    pub normals: Vec<Float3>,
+    pub uvs: Vec<Float2>,
+    pub triangles: Vec<ObjTriangle>,
+    /// Names of the objects (`o`). Triangles before the first object belong to "default".
+    pub objects: Vec<String>,
+    /// The names of the groups (`g`) each triangle belongs to. A `g` statement may put the following triangles into
+    /// several groups at once. Triangles before the first group belong to "default".
+    pub groups: Vec<Vec<String>>,
+    /// Names of the materials used by the triangles (`usemtl`)
+    pub materials: Vec<String>,
+    /// Material library files referenced by the mesh (`mtllib`)
+    pub material_libraries: Vec<String>,
+}
+
+impl ObjMesh {
+    /// Flatten the mesh into a model with three vertices per triangle.
+    ///
+    /// The materials used by the mesh are looked up by name in `materials`, e.g. loaded from the material libraries
+    /// of the mesh. The normals (texture coordinates) of the model are empty, unless every vertex of every triangle
+    /// references one. The triangle colors are the diffuse color of the material, the average of the vertex colors
+    /// or white, in that order of preference. The smoothing groups are kept if the mesh has any `s` statement, with
+    /// smoothing off for the triangles before the first one. Meshes without `s` statements are smoothed entirely when
+    /// computing normals.
+    pub fn to_model(&self, materials: &[Material]) -> Model {
+        let corners = || self.triangles.iter().flat_map(|triangle| triangle.vertices.iter());
+
+        let vertices = corners().map(|v| self.positions[v.position]).collect();
+        let normals = corners().map(|v| v.normal.map(|i| self.normals[i])).collect::<Option<_>>();
+        let uvs = corners().map(|v| v.uv.map(|i| self.uvs[i])).collect::<Option<_>>();
+
+        // Only keep the materials used by the mesh, in the order of `self.materials`
+        let mut model_materials = Vec::new();
+        let material_indices: Vec<Option<usize>> = self.materials.iter().map(|name| {
+            let material = materials.iter().find(|m| &m.name == name)?;
+            model_materials.push(material.clone());
+            Some(model_materials.len() - 1)
+        }).collect();
+        let triangle_materials: Vec<Option<usize>> = match model_materials.is_empty() {
+            true => Vec::new(),
+            false => self.triangles.iter().map(|triangle| triangle.material.and_then(|m| material_indices[m])).collect(),
+        };
+
+        let triangle_colors = self.triangles.iter().enumerate().map(|(i, triangle)| {
+            if let Some(material) = triangle_materials.get(i).copied().flatten() {
+                model_materials[material].diffuse
+            } else if !self.colors.is_empty() {
+                triangle.vertices.iter().map(|v| self.colors[v.position]).fold(Float3::zeros(), |sum, c| sum + c) / 3.0
+            } else {
+                Float3::splat(1.0)
+            }
+        }).collect();
+
+        let has_smoothing_groups = self.triangles.iter().any(|triangle| triangle.smoothing_group.is_some());
+        let triangle_smoothing_groups = match has_smoothing_groups {
+            true => self.triangles.iter().map(|triangle| triangle.smoothing_group.unwrap_or(0)).collect(),
+            false => Vec::new(),
+        };
+
+        Model {
+            vertices,
+            normals: normals.unwrap_or_default(),
+            uvs: uvs.unwrap_or_default(),
+            triangle_colors,
+            materials: model_materials,
+            triangle_materials,
+            triangle_smoothing_groups,
+            transform: Transform::empty(),
+        }
+    }
+}
+
+/// Parse the contents of an OBJ file into an indexed mesh.
+///
+/// Supports vertices (`v`, optionally with a weight or a vertex color), normals (`vn`), texture coordinates (`vt`),
+/// polygonal faces (`f`) with positive or negative (relative) indices, objects (`o`), groups (`g`), smoothing groups
+/// (`s`), materials (`usemtl`, `mtllib`), comments and line continuations. Other statements, e.g. for free-form
+/// geometry, are ignored.
+pub fn load_obj_file(obj_str: &str) -> Result<ObjMesh> {
+    let mut mesh = ObjMesh::default();
+    let mut object = None;
+    let mut groups = None;
+    let mut material = None;
+    let mut smoothing_group = None;
+    // Vertex colors are only stored once the first vertex with a color shows up
+    let mut has_colors = false;
+
+    for (line_number, line) in logical_lines(obj_str) {
+        let tokens = tokenize(&line);
+        let Some((keyword, arguments)) = tokens.split_first() else {
+            continue;
+        };
+        let error = |(column, message): TokenError| Error::parse(line_number, column, message);
+        let statement_error = |message: String| Error::parse(line_number, keyword.column, message);
+        // Names may contain spaces
+        let name = join(arguments);
+
+        match keyword.text {
+            "v" => {
+                let values = parse_floats(arguments).map_err(error)?;
+                let (position, color) = match values.len() {
+                    3 | 4 => (Float3::new(values[0], values[1], values[2]), None),
+                    6 => (Float3::new(values[0], values[1], values[2]), Some(Float3::new(values[3], values[4], values[5]))),
+                    _ => return Err(statement_error(format!("Expected 3, 4 or 6 values for a vertex, found {}", values.len()))),
+                };
+
+                if let Some(color) = color {
+                    if !has_colors {
+                        // Earlier vertices without a color default to white
+                        mesh.colors.resize(mesh.positions.len(), Float3::splat(1.0));
+                        has_colors = true;
+                    }
+                    mesh.colors.push(color);
+                } else if has_colors {
+                    mesh.colors.push(Float3::splat(1.0));
+                }
+                mesh.positions.push(position);
+            }
+            "vn" => {
+                let values = parse_floats(arguments).map_err(error)?;
+                if values.len() != 3 {
+                    return Err(statement_error(format!("Expected 3 values for a normal, found {}", values.len())));
+                }
+                mesh.normals.push(Float3::new(values[0], values[1], values[2]));
+            }
+            "vt" => {
+                // The optional third coordinate is only used by 3D textures
+                let values = parse_floats(arguments).map_err(error)?;
+                if values.is_empty() || values.len() > 3 {
+                    return Err(statement_error(format!("Expected 1 to 3 values for texture coordinates, found {}", values.len())));
+                }
+                mesh.uvs.push(Float2::new(values[0], values.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(statement_error(format!("A face needs at least 3 vertices, found {}", arguments.len())));
+                }
+                let face = arguments.iter()
+                                    .map(|a| parse_face_vertex(a.text, &mesh).map_err(|message| (a.column, message)))
+                                    .collect::<Result<Vec<_>, _>>()
+                                    .map_err(error)?;
+
+                let object = *object.get_or_insert_with(|| intern(&mut mesh.objects, "default".to_string()));
+                let groups = *groups.get_or_insert_with(|| intern(&mut mesh.groups, vec!["default".to_string()]));
+
                 // If the face has more than 3 vertices, we build n-gon triangle fan
-                if idx >= 3 {
-                    triangle_vertices.push(triangle_vertices[triangle_vertices.len() - (3*idx - 6)]);
-                    triangle_vertices.push(triangle_vertices[triangle_vertices.len() - 2]);
+                for idx in 1..face.len() - 1 {
+                    mesh.triangles.push(ObjTriangle {
+                        vertices: [face[0], face[idx], face[idx + 1]],
+                        object,
+                        groups,
+                        material,
+                        smoothing_group,
+                    });
                 }
-                triangle_vertices.push(vertices[point_idx]);
             }
+            "o" => object = Some(intern(&mut mesh.objects, name)),
+            "g" => {
+                let names = match arguments {
+                    [] => vec!["default".to_string()],
+                    _ => arguments.iter().map(|a| a.text.to_string()).collect(),
+                };
+                groups = Some(intern(&mut mesh.groups, names));
+            }
+            "s" => {
+                smoothing_group = Some(match arguments {
+                    [] => 0,
+                    [argument] if argument.text == "off" => 0,
+                    [argument] => argument.text.parse()
+                                               .map_err(|_| error((argument.column, format!("Invalid smoothing group \"{}\"", name))))?,
+                    _ => return Err(statement_error(format!("Invalid smoothing group \"{}\"", name))),
+                })
+            }
+            "usemtl" => material = Some(intern(&mut mesh.materials, name)),
+            "mtllib" => mesh.material_libraries.extend(arguments.iter().map(|a| a.text.to_string())),
+            _ => (),
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Parse a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_face_vertex(argument: &str, mesh: &ObjMesh) -> Result<ObjVertex, String> {
+    let mut parts = argument.split('/');
+    let position = parts.next().unwrap_or_default();
+    let uv = parts.next().filter(|s| !s.is_empty());
+    let normal = parts.next().filter(|s| !s.is_empty());
+    if parts.next().is_some() {
+        return Err(format!("Invalid face vertex \"{}\"", argument));
+    }
+
+    Ok(ObjVertex {
+        position: resolve_index(position, mesh.positions.len(), "vertex")?,
+        uv: uv.map(|i| resolve_index(i, mesh.uvs.len(), "texture coordinate")).transpose()?,
+        normal: normal.map(|i| resolve_index(i, mesh.normals.len(), "normal")).transpose()?,
+    })
+}
+
+/// Turn a 1-based OBJ index into a 0-based index. Negative indices count back from the last of the `count`
+/// elements defined so far.
+fn resolve_index(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let value: i64 = index.parse().map_err(|_| format!("Invalid {} index \"{}\"", kind, index))?;
+    let resolved = match value {
+        1.. => value - 1,
+        ..0 => count as i64 + value,
+        0 => return Err(format!("Invalid {} index 0, indices start at 1", kind)),
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("The {} index {} is out of range, {} defined so far", kind, value, count));
+    }
+    Ok(resolved as usize)
+}
+
+/// Index of `value` in `values`, adding it if it is not present yet
+fn intern<T: PartialEq>(values: &mut Vec<T>, value: T) -> usize {
+    match values.iter().position(|v| *v == value) {
+        Some(index) => index,
+        None => {
+            values.push(value);
+            values.len() - 1
         }
     }
-    triangle_vertices
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The normals computed for two triangles meeting at a right angle, one facing +z and the other +y, with the
+    /// given smoothing statements before each of them
+    fn smoothed_normals(first: &str, second: &str) -> Vec<Float3> {
+        let obj = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\n{}\nf 1 2 3\n{}\nf 1 4 2\n", first, second);
+        let mut model = load_obj_file(&obj).unwrap().to_model(&[]);
+        model.validate().unwrap();
+        model.compute_normals();
+        model.normals
+    }
+
+    #[test]
+    fn computed_normals_follow_the_smoothing_groups() {
+        let (z, y) = (Float3::new(0.0, 0.0, 1.0), Float3::new(0.0, 1.0, 0.0));
+        let shared = Float3::new(0.0, 1.0, 1.0).normalize();
+        let smooth = vec![shared, shared, z, shared, y, shared];
+        let faceted = vec![z, z, z, y, y, y];
+
+        // Without smoothing groups, all triangles are smoothed together
+        assert_eq!(smoothed_normals("", ""), smooth);
+        assert_eq!(smoothed_normals("s 1", ""), smooth);
+        assert_eq!(smoothed_normals("s 2", "s 1"), faceted);
+        assert_eq!(smoothed_normals("s 1", "s off"), faceted);
+        assert_eq!(smoothed_normals("s 0", "s 3"), faceted);
+        // Smoothing turned off explicitly is not the same as no smoothing groups
+        assert_eq!(smoothed_normals("s off", ""), faceted);
+        assert_eq!(smoothed_normals("s 0", "s 0"), faceted);
+        assert_eq!(smoothed_normals("", "s 1"), faceted);
+    }
+
+    #[test]
+    fn group_statements_may_name_several_groups() {
+        let mesh = load_obj_file("v 0 0 0\nf 1 1 1\ng a b\nf 1 1 1\ng b\nf 1 1 1\ng\nf 1 1 1\ng a  b\nf 1 1 1").unwrap();
+        assert_eq!(mesh.groups, [vec!["default"], vec!["a", "b"], vec!["b"]]);
+        let groups: Vec<_> = mesh.triangles.iter().map(|triangle| triangle.groups).collect();
+        assert_eq!(groups, [0, 1, 2, 0, 1]);
+    }
+}
@@ -0,0 +1,12 @@
+//! A CPU rasterizer rendering triangle meshes to images.
+//!
+//! Models are loaded with `formats`, rendered with `rendering::pipeline` into a `rendering::RenderTarget` and written
+//! to disk with `rendering::bitmap`.
+
+pub mod error;
+pub mod formats;
+pub mod objects;
+pub mod rendering;
+pub mod vector_math;
+
+pub use crate::error::{Error, Result};
@@ -1,219 +1,107 @@
-use rand::{rng, rngs::ThreadRng, Rng};
-use std::fs::{read_to_string, write};
-use std::io;
-use std::iter::zip;
-
-mod formats;
-mod objects;
-mod rendering;
-mod vector_math;
-
-use crate::objects::Model;
-use crate::rendering::bitmap::image_to_bmp_buffer;
-use crate::rendering::RenderTarget;
-use crate::rendering::pipeline;
-use crate::rendering::transforms::Transform;
-use crate::vector_math::triangle::*;
-use crate::vector_math::vector::*;
-
-const WIDTH: usize = 512;
-const HEIGHT: usize = 512;
-
-struct Scene {
-    vertices: Vec<Float2>,
-    vertex_velocities: Vec<Float2>,
-    triangle_colors: Vec<Float3>
-}
-
-fn main() {
-    let mut model = load_suzanne_model();
-    let mut render_target = RenderTarget::new(WIDTH, HEIGHT);
-    let fov = 60.0;
-
-    for i in 0..20 {
-        pipeline::render3d(&model, &mut render_target, fov);
-    
-        // Save the current stage of the image buffer to a bitmap
-        let file_name = format!("images/monkey_frame_{:03}.bmp", i);
-        match write_image_to_file(&render_target.image_buffer, file_name.to_string()) {
-            Err(why) => panic!("Failed to write frame {} to file {}: {}", 0, file_name, why),
-            Ok(_) => (),
-        };
-
-        model.transform.yaw += 0.1;
-        model.transform.pitch += 0.02;
-        render_target.clear();
-    }
-}
-
-#[allow(dead_code)]
-fn old_main() {
-    let mut scene = create_test_images();
-    let mut render_target = RenderTarget::new(WIDTH, HEIGHT);
-
-    for i in 0..5 {
-        pipeline::render2d(&scene.vertices, &scene.triangle_colors, &mut render_target);
-    
-        // Save the current stage of the image buffer to a bitmap
-        let file_name = format!("images/test_frame_{:03}.bmp", i);
-        match write_image_to_file(&render_target.image_buffer, file_name.to_string()) {
-            Err(why) => panic!("Failed to write frame {} to file {}: {}", 0, file_name, why),
-            Ok(_) => (),
-        };
-
-        update(&mut scene.vertices, &mut scene.vertex_velocities, 0.25);
-        render_target.clear();
-    }
-}
-
-fn update(vertices: &mut Vec<Float2>, velocities: &mut Vec<Float2>, delta_t: f64) {
-    for (vert, vel) in zip(vertices, velocities) {
-        *vert += &*vel * delta_t;
-        // Flip the velocities, if the points end up outside the render box
-        if vert.x < 0f64 || vert.x > WIDTH as f64{
-            vel.x *= -1f64;
+mod cli;
+
+use std::fs::create_dir_all;
+use std::path::Path;
+use std::process::ExitCode;
+
+use software_rasterizer::{Error, Result};
+use software_rasterizer::formats::load_model;
+use software_rasterizer::rendering::RenderTarget;
+use software_rasterizer::rendering::image::DepthBuffer;
+use software_rasterizer::rendering::image_format::write_image_file;
+use software_rasterizer::rendering::lighting::{Light, LightingShader};
+use software_rasterizer::rendering::pfm::write_depth_pfm_file;
+use software_rasterizer::rendering::pipeline::{self, RenderSettings};
+use software_rasterizer::rendering::transforms::{Camera, rotation_from_angles};
+use software_rasterizer::vector_math::vector::Float3;
+
+use crate::cli::{Command, Options, USAGE};
+
+fn main() -> ExitCode {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
         }
-        if vert.y < 0f64 || vert.y > HEIGHT as f64 {
-            vel.y *= -1f64;
-        }
-    }
-}
-
-#[allow(dead_code)]
-fn load_cube_model() -> Model {
-    let mut model = load_model("models/cube.obj");
-    model.transform.position += Float3::new(0.0, 0.0, 5.0);
-    model
-}
-
-fn load_suzanne_model() -> Model {
-    let mut model = load_model("models/suzanne.obj");
-    model.transform.position += Float3::new(0.0, 0.0, 3.0);
-    model
-}
-
-fn load_model(obj_file: &str) -> Model {
-    let model_vertices = match read_to_string(obj_file) {
-        Ok(obj_str) => crate::formats::obj_format::load_obj_file(obj_str),
-        Err(why) => match why.kind() {
-            io::ErrorKind::NotFound => panic!("The path {} is non-existent! Make sure the folder structure exists.", obj_file),
-            io::ErrorKind::PermissionDenied => panic!("You don't have permissions to write to file \"{}\"", obj_file),
-            _ => panic!("Failed to open file {}: {}", obj_file, why),
+        Err(message) => {
+            eprintln!("Error: {}\nRun with '--help' for the usage.", message);
+            return ExitCode::from(2);
         }
     };
 
-    // Randomize the triangle colors
-    let mut g = rng();
-    let mut triangle_colors: Vec<Float3> = Vec::new();
-    for _ in 0..(model_vertices.len() / 3) {
-        triangle_colors.push(random_color(&mut g))
-    }
-
-    Model { 
-        vertices: model_vertices, 
-        triangle_colors, 
-        transform: Transform::empty()
+    match render(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(why) => {
+            eprintln!("Error: {}", why);
+            ExitCode::FAILURE
+        }
     }
 }
 
-#[allow(dead_code)]
-/// Generate randomly initialized triangles
-fn create_test_images() -> Scene {
-    // Get the random vertices, triangle velocities and colors
-    let (points, velocities, triangle_colors) = setup_triangles(WIDTH, HEIGHT);
-
-    Scene { vertices: points, vertex_velocities: velocities, triangle_colors: triangle_colors }
-}
-
-/// Initialize triangles with random positions, velocities and colors. Returns the flattened vertices vector, the velocities and the triangle colors.
-fn setup_triangles(screen_width: usize, screen_height: usize) -> (Vec<Float2>, Vec<Float2>, Vec<Float3>) {
-    const TRIANGLE_COUNT: usize = 50;
-
-    // Initialize data arrays with 0-vectors
-    let mut points = vec![Float2::zeros(); TRIANGLE_COUNT * 3];
-    let mut velocities = vec![Float2::zeros(); points.len()];
-    let mut triangle_colors = vec![Float3::zeros(); TRIANGLE_COUNT];
-
-    // Compute the image center
-    let center = Float2::new((screen_width / 2) as f64, (screen_height / 2) as f64);
-
-    // Initialize a randomizer
-    let mut g = rng();
-
-    // Generate the random positions of each vertex in the x/y range 0.35..0.65 (widths/heights)
-    for p in points.iter_mut() {
-        let random_point = random_float2(&mut g, screen_width, screen_height);
-        let offset = (&random_point - &center) * 0.3;
-        *p = &center + &offset;
-    }
-
-    // Generate random velocities for each triangle
-    for i in (0..velocities.len()).step_by(3) {
-        let random_velocity = random_float2(&mut g, screen_width, screen_height);
-        let velocity = (&random_velocity - &center) * 0.5;
-        velocities[i + 0] = velocity.clone();
-        velocities[i + 1] = velocity.clone();
-        velocities[i + 2] = velocity;
-    }
+/// Render all frames of the animation described by the options
+fn render(options: &Options) -> Result<()> {
+    let mut model = load_model(&options.input)?;
+    let factor = options.supersampling;
+    // The supersampled size and the number of samples in it must not overflow
+    let too_large = || Error::ImageTooLarge { width: options.width, height: options.height };
+    let width = options.width.checked_mul(factor).ok_or_else(too_large)?;
+    let height = options.height.checked_mul(factor).ok_or_else(too_large)?;
+    width.checked_mul(height).and_then(|size| size.checked_mul(options.sample_count.get_count())).ok_or_else(too_large)?;
+    let mut render_target = RenderTarget::new_multisampled(width, height, options.sample_count);
+    let mut camera = Camera::new(options.camera_position, options.fov);
+    camera.look_at(&options.camera_target, &Float3::new(0.0, 1.0, 0.0));
+    let settings = RenderSettings {
+        threads: options.threads,
+        precision: options.precision,
+        ..RenderSettings::default()
+    };
+    let light = Light::Directional { direction: Float3::new(-1.0, -1.0, 1.0), color: Float3::splat(1.0), intensity: 1.0 };
+    let shader = LightingShader::new(vec![light], options.shading_mode, camera.position);
+
+    for frame in 0..options.frames {
+        render_target.clear_to(&options.background);
+        pipeline::render3d(&model, &shader, &shader, &mut render_target, &camera, &settings)?;
+
+        let path = options.get_output_path(frame);
+        create_parent_directory(&path)?;
+        let alpha = options.alpha.then(|| render_target.get_alpha());
+        if factor > 1 {
+            // Downsample the supersampled frame to the output size
+            let image = render_target.image_buffer.resample(options.width, options.height, options.filter);
+            let alpha = alpha.map(|alpha| alpha.resample(options.width, options.height, options.filter));
+            write_image_file(&image, alpha.as_ref(), options.format, &path)?;
+        } else {
+            write_image_file(&render_target.image_buffer, alpha.as_ref(), options.format, &path)?;
+        }
+        if let Some(depth_path) = options.get_depth_output_path(frame) {
+            create_parent_directory(&depth_path)?;
+            write_depth_pfm_file(&closest_depth(&render_target.depth_buffer, factor), &depth_path)?;
+        }
 
-    // Assign a random color to each triangle
-    for c in triangle_colors.iter_mut() {
-        *c = random_color(&mut g);
+        let angles = options.rotation * (frame + 1) as f64;
+        model.transform.rotation = rotation_from_angles(angles.y.to_radians(), angles.x.to_radians(), 0.0);
     }
-
-    // Return the vertices, velocities and colors
-    (points, velocities, triangle_colors)
-}
-
-/// Generate a random Float2 with x/y ranges `0..width` and `0..height`.
-fn random_float2(rng: &mut ThreadRng, width: usize, height: usize) -> Float2 {
-    Float2::new(rng.random_range(0..width) as f64, rng.random_range(0..height) as f64)
-}
-
-/// Generate a random RGB color.
-fn random_color(rng: &mut ThreadRng) -> Float3 {
-    Float3::new(
-        rng.random_range(0.0..1.0),
-        rng.random_range(0.0..1.0),
-        rng.random_range(0.0..1.0)
-    )
+    Ok(())
 }
 
-#[allow(dead_code)]
-fn create_test_image() -> () {
-    let mut image = rendering::image::ImageBuffer::new(WIDTH, HEIGHT);
-    
-    let a = Float2::new(0.2 * WIDTH as f64, 0.2 * HEIGHT as f64);
-    let b = Float2::new(0.7 * WIDTH as f64, 0.4 * HEIGHT as f64);
-    let c = Float2::new(0.4 * WIDTH as f64, 0.8 * HEIGHT as f64);
-
-    for y in 0..HEIGHT {
-        for x in 0..WIDTH {
-
-            let p = Float2::new(x as f64, y as f64);
-
-            let (inside, _) = point_in_triangle(&a, &b, &c, &p);
-            if inside {
-                image[[x, y]] = Float3::new(0.0, 0.0, 1.0);
-            }
+/// Shrink the depth buffer by the factor, keeping the closest depth of each block of pixels. Filtering the depths
+/// would blend surfaces with the infinite depth of the background.
+fn closest_depth(depth: &DepthBuffer, factor: usize) -> DepthBuffer {
+    let mut closest = DepthBuffer::new(depth.get_width() / factor, depth.get_height() / factor);
+    for y in 0..depth.get_height() {
+        for x in 0..depth.get_width() {
+            let pixel = &mut closest[[x / factor, y / factor]];
+            *pixel = pixel.min(depth[[x, y]]);
         }
     }
-
-    let file_name = "test_image.bmp";
-    match write_image_to_file(&image, file_name.to_string()) {
-        Ok(_) => (),
-        Err(why) => match why.kind() {
-            io::ErrorKind::NotFound => panic!("The path {} is non-existent! Make sure the folder structure exists.", file_name.to_string()),
-            io::ErrorKind::PermissionDenied => panic!("You don't have permissions to write to file \"{}\"", file_name.to_string()),
-            _ => panic!("Failed to write to file {}: {}", file_name, why),
-        }
-    };
+    closest
 }
 
-fn write_image_to_file(image: &rendering::image::ImageBuffer, name: String) -> Result<(), io::Error> {
-    let Ok(bmp_buffer) = image_to_bmp_buffer(&image) else {
-        panic!("Failed to convert image to bitmap!");
-    };
-    write(name, &bmp_buffer)?;
+/// Create the directory the file at `path` goes into, if it does not exist yet
+fn create_parent_directory(path: &Path) -> Result<()> {
+    if let Some(directory) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        create_dir_all(directory).map_err(|why| Error::from(why).in_file(directory))?;
+    }
     Ok(())
-}
\ No newline at end of file
+}
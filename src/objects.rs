@@ -1,8 +1,174 @@
-use crate::vector_math::vector::Float3;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::vector_math::vector::{Float2, Float3};
+use crate::rendering::texture::Texture;
 use crate::rendering::transforms::Transform;
 
+/// Surface properties of a group of triangles, as described by an MTL file
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    /// Fraction of the ambient light reflected (`Ka`)
+    pub ambient: Float3,
+    /// Diffuse surface color (`Kd`)
+    pub diffuse: Float3,
+    /// Color of the specular highlights (`Ks`)
+    pub specular: Float3,
+    /// Specular exponent (`Ns`)
+    pub shininess: f64,
+    /// 1 for opaque surfaces and 0 for fully transparent surfaces (`d`, or 1 - `Tr`). The rasterizer does not blend,
+    /// so this is not used for rendering.
+    pub opacity: f64,
+    /// Illumination model (`illum`). 0 shows the diffuse color without lighting, 1 lights the surface without
+    /// specular highlights, and 2 and above light it with specular highlights.
+    pub illumination_model: u32,
+    /// Path of the texture multiplied with the diffuse color (`map_Kd`)
+    pub diffuse_map: Option<PathBuf>,
+    /// The texture loaded from `diffuse_map`
+    pub diffuse_texture: Option<Arc<Texture>>,
+    /// Path of the specular color texture (`map_Ks`). It is not loaded or used for rendering.
+    pub specular_map: Option<PathBuf>,
+    /// Path of the bump map (`map_Bump` or `bump`). It is not loaded or used for rendering.
+    pub bump_map: Option<PathBuf>,
+}
+
+impl Material {
+    /// Creates a white material with the default values of the MTL format
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: Float3::splat(1.0),
+            diffuse: Float3::splat(1.0),
+            specular: Float3::zeros(),
+            shininess: 0.0,
+            opacity: 1.0,
+            illumination_model: 2,
+            diffuse_map: None,
+            diffuse_texture: None,
+            specular_map: None,
+            bump_map: None,
+        }
+    }
+
+    /// Whether the surface is lit by the light sources, rather than showing its diffuse color as is
+    pub fn is_lit(&self) -> bool {
+        self.illumination_model != 0
+    }
+
+    /// Whether the lit surface has specular highlights
+    pub fn has_highlights(&self) -> bool {
+        self.illumination_model >= 2
+    }
+}
+
 pub struct Model {
     pub vertices: Vec<Float3>,
+    /// Normal of each vertex in model space. Empty if the model has no normals.
+    pub normals: Vec<Float3>,
+    /// Texture coordinates of each vertex. Empty if the model has no texture coordinates.
+    pub uvs: Vec<Float2>,
     pub triangle_colors: Vec<Float3>,
+    pub materials: Vec<Material>,
+    /// Index into `materials` for each triangle. Empty if the model has no materials.
+    pub triangle_materials: Vec<Option<usize>>,
+    /// Smoothing group of each triangle, which decides the normals `compute_normals` gives its vertices. Empty if
+    /// all triangles are smoothed together.
+    pub triangle_smoothing_groups: Vec<u32>,
     pub transform: Transform,
-}
\ No newline at end of file
+}
+
+impl Model {
+    /// Material of the triangle with the given index, if it has one
+    pub fn get_material(&self, triangle: usize) -> Option<&Material> {
+        let index = (*self.triangle_materials.get(triangle)?)?;
+        self.materials.get(index)
+    }
+
+    /// Check that the buffers of the model fit together, so rendering it cannot index out of bounds
+    pub fn validate(&self) -> Result<()> {
+        let vertex_count = self.vertices.len();
+        let triangle_count = vertex_count / 3;
+        let invalid = |message: String| Err(Error::InvalidModel(message));
+
+        if !vertex_count.is_multiple_of(3) {
+            return invalid(format!("the vertex count {} is not a multiple of 3", vertex_count));
+        }
+        if !self.normals.is_empty() && self.normals.len() != vertex_count {
+            return invalid(format!("{} normals for {} vertices", self.normals.len(), vertex_count));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != vertex_count {
+            return invalid(format!("{} texture coordinates for {} vertices", self.uvs.len(), vertex_count));
+        }
+        if self.triangle_colors.len() != triangle_count {
+            return invalid(format!("{} triangle colors for {} triangles", self.triangle_colors.len(), triangle_count));
+        }
+        if !self.triangle_materials.is_empty() && self.triangle_materials.len() != triangle_count {
+            return invalid(format!("{} triangle materials for {} triangles", self.triangle_materials.len(), triangle_count));
+        }
+        if !self.triangle_smoothing_groups.is_empty() && self.triangle_smoothing_groups.len() != triangle_count {
+            return invalid(format!("{} smoothing groups for {} triangles", self.triangle_smoothing_groups.len(), triangle_count));
+        }
+        if let Some(index) = self.triangle_materials.iter().flatten().find(|&&i| i >= self.materials.len()) {
+            return invalid(format!("material index {} out of range, the model has {} materials", index, self.materials.len()));
+        }
+        Ok(())
+    }
+
+    /// Normal of the triangle containing the vertex at `index`, in model space
+    pub fn get_face_normal(&self, index: usize) -> Float3 {
+        let first = index - index % 3;
+        face_normal(&self.vertices[first], &self.vertices[first + 1], &self.vertices[first + 2])
+    }
+
+    /// Smoothing group of the triangle with the given index. 0 means the triangle is not smoothed.
+    pub fn get_smoothing_group(&self, triangle: usize) -> u32 {
+        // Without smoothing groups, all triangles are smoothed together
+        self.triangle_smoothing_groups.get(triangle).copied().unwrap_or(1)
+    }
+
+    /// Compute smooth vertex normals from the faces, replacing the current normals.
+    ///
+    /// Vertices at the same position in the same smoothing group share the average of the normals of the faces
+    /// of that group around them, weighted by the face areas. The vertices of triangles in group 0 get the face
+    /// normal.
+    pub fn compute_normals(&mut self) {
+        // Accumulate the (area-weighted) face normals at each distinct position in each smoothing group
+        let mut normal_sums: HashMap<([u64; 3], u32), Float3> = HashMap::new();
+        for i in (0..self.vertices.len()).step_by(3) {
+            let group = self.get_smoothing_group(i / 3);
+            if group == 0 {
+                continue;
+            }
+            let [a, b, c] = [self.vertices[i], self.vertices[i + 1], self.vertices[i + 2]];
+            let weighted_normal = (b - a).cross(&(c - a));
+            for vertex in [a, b, c] {
+                *normal_sums.entry((position_key(&vertex), group)).or_insert(Float3::zeros()) += weighted_normal;
+            }
+        }
+
+        self.normals = self.vertices.iter().enumerate().map(|(i, v)| {
+            let n = match self.get_smoothing_group(i / 3) {
+                0 => {
+                    let [a, b, c] = [0, 1, 2].map(|corner| self.vertices[i - i % 3 + corner]);
+                    (b - a).cross(&(c - a))
+                }
+                group => normal_sums[&(position_key(v), group)],
+            };
+            if n.length() > 0.0 { n.normalize() } else { n }
+        }).collect();
+    }
+}
+
+/// Unit normal of the triangle `a`->`b`->`c`, pointing towards the side from which the winding looks
+/// counter-clockwise in a right-handed coordinate system (the OBJ convention)
+fn face_normal(a: &Float3, b: &Float3, c: &Float3) -> Float3 {
+    (b - a).cross(&(c - a)).normalize()
+}
+
+/// Key identifying vertices at exactly the same position
+fn position_key(v: &Float3) -> [u64; 3] {
+    [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]
+}
@@ -1,53 +1,468 @@
+use std::fs::{read, write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::image::{quantize, AlphaBuffer, DecodedImage, ImageBuffer};
 use crate::vector_math::vector::Float3;
-use crate::rendering::image::ImageBuffer;
 
-pub fn image_to_bmp_buffer(image: &ImageBuffer) -> Result<Vec<u8>, <usize as TryInto<u32>>::Error> {
+const FILE_HEADER_SIZE: u32 = 14;
+/// Size of the BITMAPINFOHEADER, used for 24-bit files
+const INFO_HEADER_SIZE: u32 = 40;
+/// Size of the BITMAPV5HEADER, which adds the channel masks needed for the alpha channel of 32-bit files
+const V5_HEADER_SIZE: u32 = 124;
+
+/// Uncompressed pixels in the order BGR
+const BI_RGB: u32 = 0;
+/// Run-length encoded palette indices
+const BI_RLE8: u32 = 1;
+/// Uncompressed pixels with the channel positions given by masks
+const BI_BITFIELDS: u32 = 3;
+/// Most pixels RLE8 data can describe per byte: runs of up to 255 pixels in two bytes, rounded up
+const RLE8_MAX_PIXELS_PER_BYTE: usize = 128;
+/// The color space tag 'sRGB'
+const LCS_SRGB: u32 = 0x7352_4742;
+/// Rendering intent preserving saturation, the default for images
+const LCS_GM_IMAGES: u32 = 4;
+/// 72 DPI in pixels per meter
+const PIXELS_PER_METER: u32 = 2835;
+
+/// Order of the rows in a BMP file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RowOrder {
+    /// The last row of the image comes first. Every reader supports this order.
+    #[default]
+    BottomUp,
+    /// The first row of the image comes first, marked by a negative height
+    TopDown,
+}
+
+/// Write the image to a BMP file. Without alpha plane the file has 24 bits per pixel, with one it has 32 bits.
+pub fn write_bmp_file(image: &ImageBuffer, alpha: Option<&AlphaBuffer>, row_order: RowOrder, path: impl AsRef<Path>) -> Result<()> {
+    let bmp_buffer = image_to_bmp_buffer(image, alpha, row_order)?;
+    write(path.as_ref(), &bmp_buffer).map_err(|why| Error::from(why).in_file(path.as_ref()))
+}
+
+/// Encode the image as a BMP file. Without alpha plane the pixels are stored as 24-bit BGR, with one, which must have
+/// the size of the image, as 32-bit BGRA with a BITMAPV5HEADER describing the channels.
+pub fn image_to_bmp_buffer(image: &ImageBuffer, alpha: Option<&AlphaBuffer>, row_order: RowOrder) -> Result<Vec<u8>> {
     if image.get_size() == 0 {
-        panic!("Image has zero width!")
+        return Err(Error::EmptyImage);
+    }
+    if let Some(alpha) = alpha && (alpha.get_width(), alpha.get_height()) != (image.get_width(), image.get_height()) {
+        return Err(Error::InvalidImage(format!(
+            "the alpha plane is {}x{}, but the image is {}x{}",
+            alpha.get_width(), alpha.get_height(), image.get_width(), image.get_height()
+        )));
     }
-    let image_width: u32 = image.get_width().try_into()?;
-    let image_height: u32 = image.get_height().try_into()?;
 
-    let byte_counts = [14, 40, 4*image_width*image_height];
+    let (bits_per_pixel, info_header_size) = if alpha.is_some() { (32u16, V5_HEADER_SIZE) } else { (24, INFO_HEADER_SIZE) };
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    // Rows are padded to a multiple of 4 bytes
+    let row_size = (image.get_width() * bytes_per_pixel).div_ceil(4) * 4;
+    let padding = row_size - image.get_width() * bytes_per_pixel;
 
-    let mut buffer: Vec<u8> = Vec::new();
-    // --- Headers ---
-    // BMP header start
+    let too_large = || Error::ImageTooLarge { width: image.get_width(), height: image.get_height() };
+    // BMP stores the dimensions as signed 32-bit integers and the sizes as unsigned ones
+    let width: i32 = image.get_width().try_into().map_err(|_| too_large())?;
+    let height: i32 = image.get_height().try_into().map_err(|_| too_large())?;
+    let data_offset = FILE_HEADER_SIZE + info_header_size;
+    let data_size: u32 = row_size.checked_mul(image.get_height())
+                                 .and_then(|size| size.try_into().ok())
+                                 .filter(|&size| size <= u32::MAX - data_offset)
+                                 .ok_or_else(too_large)?;
+
+    let mut buffer: Vec<u8> = Vec::with_capacity((data_offset + data_size) as usize);
+    // --- File header ---
     buffer.extend(b"BM");
     // Total file size
-    buffer.extend(byte_counts.iter().sum::<u32>().to_ne_bytes());
-    // Mark image as manually created
-    buffer.extend(0u32.to_ne_bytes());
-    // Data offset from start byte
-    buffer.extend(byte_counts[0..2].iter().sum::<u32>().to_ne_bytes());
-    // DIP header size
-    buffer.extend(byte_counts[1].to_ne_bytes());
-    // Image width
-    buffer.extend(image_width.to_ne_bytes());
-    // Image height
-    buffer.extend(image_height.to_ne_bytes());
+    buffer.extend((data_offset + data_size).to_le_bytes());
+    // Two reserved fields
+    buffer.extend(0u32.to_le_bytes());
+    // Offset of the pixel data from the start of the file
+    buffer.extend(data_offset.to_le_bytes());
+
+    // --- Info header ---
+    buffer.extend(info_header_size.to_le_bytes());
+    buffer.extend(width.to_le_bytes());
+    // A negative height marks top-down files
+    let signed_height = match row_order {
+        RowOrder::BottomUp => height,
+        RowOrder::TopDown => -height,
+    };
+    buffer.extend(signed_height.to_le_bytes());
     // Number of color planes, must be 1
-    buffer.extend(1u16.to_ne_bytes());
-    // Bits per pixel (1 byte per color channel and one for alignment)
-    buffer.extend((8 * 4u16).to_ne_bytes());  // TODO: Change back to 4u32!!
-    // Set to RGB format without compression
-    buffer.extend(0u32.to_ne_bytes());
-    // Size of data
-    buffer.extend(byte_counts[2].to_ne_bytes());
-    // Print resolution and palette - we ignore that
-    buffer.extend([0u8; 16]);
-
-    // --- Write image data ---
-    for y in 0..image_height {
-        for x in 0..image_width {
-            buffer.extend(convert_to_color(&image[[x as usize, y as usize]]));
-            buffer.push(0u8);
+    buffer.extend(1u16.to_le_bytes());
+    buffer.extend(bits_per_pixel.to_le_bytes());
+    buffer.extend(if alpha.is_some() { BI_BITFIELDS } else { BI_RGB }.to_le_bytes());
+    buffer.extend(data_size.to_le_bytes());
+    // Horizontal and vertical print resolution
+    buffer.extend(PIXELS_PER_METER.to_le_bytes());
+    buffer.extend(PIXELS_PER_METER.to_le_bytes());
+    // No palette, so no used or important palette colors
+    buffer.extend([0u8; 8]);
+
+    if alpha.is_some() {
+        // Masks of the red, green, blue and alpha channels in the little-endian pixel value
+        for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+            buffer.extend(mask.to_le_bytes());
         }
+        buffer.extend(LCS_SRGB.to_le_bytes());
+        // Color space endpoints and gamma, unused with sRGB
+        buffer.extend([0u8; 36 + 12]);
+        buffer.extend(LCS_GM_IMAGES.to_le_bytes());
+        // No embedded color profile and a reserved field
+        buffer.extend([0u8; 12]);
+    }
+    debug_assert_eq!(buffer.len(), data_offset as usize);
+
+    // --- Pixel data ---
+    let rows: Box<dyn Iterator<Item = usize>> = match row_order {
+        RowOrder::BottomUp => Box::new((0..image.get_height()).rev()),
+        RowOrder::TopDown => Box::new(0..image.get_height()),
+    };
+    for y in rows {
+        for x in 0..image.get_width() {
+            let color = image[[x, y]];
+            buffer.extend([color.b(), color.g(), color.r()].map(|value| quantize(value, u8::MAX as f64) as u8));
+            if let Some(alpha) = alpha {
+                buffer.push(quantize(alpha[[x, y]], u8::MAX as f64) as u8);
+            }
+        }
+        buffer.extend(&[0u8; 3][..padding]);
     }
     Ok(buffer)
 }
 
-fn convert_to_color(v: &Float3) -> Vec<u8> {
-    let v = vec![v.b(), v.g(), v.r()];
-    v.iter().map(|f| (f * 255.0) as u8).collect()
-}
\ No newline at end of file
+/// Read a BMP file. See [`bmp_buffer_to_image`] for the supported variants.
+pub fn read_bmp_file(path: impl AsRef<Path>) -> Result<DecodedImage> {
+    let data = read(path.as_ref()).map_err(|why| Error::from(why).in_file(path.as_ref()))?;
+    bmp_buffer_to_image(&data).map_err(|why| why.in_file(path.as_ref()))
+}
+
+/// Decode a BMP file with 24 or 32 bits per pixel, or with 8 bits per pixel indexing a palette, either uncompressed or
+/// RLE8-compressed. Both row orders are supported. The image has an alpha plane if the channel masks of a 32-bit file
+/// include an alpha channel.
+///
+/// The size in the header is checked against the pixel data before allocating the image. RLE8 data describing more
+/// than 128 pixels per byte, which is only possible by skipping pixels with escapes, is rejected.
+pub fn bmp_buffer_to_image(data: &[u8]) -> Result<DecodedImage> {
+    if !data.starts_with(b"BM") {
+        return Err(Error::InvalidImage("the BMP signature is missing".to_string()));
+    }
+    let data_offset = read_u32(data, 10)? as usize;
+    let info_header_size = read_u32(data, 14)?;
+    if info_header_size < INFO_HEADER_SIZE {
+        return Err(Error::UnsupportedImage(format!("BMP info header of {} bytes", info_header_size)));
+    }
+    let signed_width = read_i32(data, 18)?;
+    let signed_height = read_i32(data, 22)?;
+    let bits_per_pixel = read_u16(data, 28)?;
+    let compression = read_u32(data, 30)?;
+    let colors_used = read_u32(data, 46)?;
+
+    if signed_width <= 0 || signed_height == 0 {
+        return Err(Error::InvalidImage(format!("the BMP size is {}x{}", signed_width, signed_height)));
+    }
+    let width = signed_width as usize;
+    let height = signed_height.unsigned_abs() as usize;
+    // Row i of the file is row y of the image
+    let row_to_y = |i: usize| if signed_height < 0 { i } else { height - 1 - i };
+    let too_large = || Error::ImageTooLarge { width, height };
+    let pixel_count = width.checked_mul(height).ok_or_else(too_large)?;
+
+    // Uncompressed rows are padded to a multiple of 4 bytes
+    let row_size = (width.checked_mul(bits_per_pixel as usize).ok_or_else(too_large)?).div_ceil(32) * 4;
+    let pixel_data = data.get(data_offset..).unwrap_or_default();
+    let rows = || -> Result<_> {
+        let size = row_size.checked_mul(height).ok_or_else(too_large)?;
+        if pixel_data.len() < size {
+            return Err(Error::InvalidImage("the BMP pixel data is truncated".to_string()));
+        }
+        Ok(pixel_data.chunks_exact(row_size).take(height))
+    };
+
+    // Check the size against the pixel data before allocating the image, so a forged header cannot exhaust the memory
+    let is_supported = matches!((bits_per_pixel, compression), (8, BI_RGB | BI_RLE8) | (24, BI_RGB) | (32, BI_RGB | BI_BITFIELDS));
+    if !is_supported {
+        return Err(Error::UnsupportedImage(format!(
+            "BMP with {} bits per pixel and compression {}", bits_per_pixel, compression
+        )));
+    }
+    if compression == BI_RLE8 {
+        if pixel_count / RLE8_MAX_PIXELS_PER_BYTE > pixel_data.len() {
+            return Err(Error::InvalidImage("the BMP run-length encoded data is too short for the image size".to_string()));
+        }
+    } else {
+        let _ = rows()?;
+    }
+
+    let mut image = ImageBuffer::new(width, height);
+    let mut alpha = None;
+    match (bits_per_pixel, compression) {
+        (8, BI_RGB | BI_RLE8) => {
+            // The palette follows the info header, with 4 bytes per color in the order BGR and one unused byte
+            let palette_size = if colors_used == 0 { 256 } else { colors_used.min(256) as usize };
+            let palette_start = (FILE_HEADER_SIZE + info_header_size) as usize;
+            let palette: Vec<Float3> = data.get(palette_start..palette_start + 4 * palette_size)
+                                           .ok_or_else(|| Error::InvalidImage("the BMP palette is truncated".to_string()))?
+                                           .chunks_exact(4)
+                                           .map(|bgr| Float3::new(bgr[2] as f64, bgr[1] as f64, bgr[0] as f64) / 255.0)
+                                           .collect();
+
+            let indices = if compression == BI_RLE8 {
+                decode_rle8(pixel_data, width, height, pixel_count)
+            } else {
+                rows()?.flat_map(|row| &row[..width]).copied().collect()
+            };
+            for (i, &index) in indices.iter().enumerate() {
+                let color = palette.get(index as usize)
+                                   .ok_or_else(|| Error::InvalidImage(format!("the palette index {} is out of range", index)))?;
+                image[[i % width, row_to_y(i / width)]] = *color;
+            }
+        }
+        (24, BI_RGB) => {
+            for (i, row) in rows()?.enumerate() {
+                for (x, bgr) in row.chunks_exact(3).take(width).enumerate() {
+                    image[[x, row_to_y(i)]] = Float3::new(bgr[2] as f64, bgr[1] as f64, bgr[0] as f64) / 255.0;
+                }
+            }
+        }
+        (32, BI_RGB | BI_BITFIELDS) => {
+            // The masks of the red, green and blue channel follow the info header or are its next fields, and newer
+            // headers add the alpha mask
+            let masks = if compression == BI_BITFIELDS {
+                let alpha_mask = if info_header_size >= 56 { read_u32(data, 66)? } else { 0 };
+                [read_u32(data, 54)?, read_u32(data, 58)?, read_u32(data, 62)?, alpha_mask]
+            } else {
+                [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0]
+            };
+            let mut opacity = (masks[3] != 0).then(|| AlphaBuffer::filled(width, height, 1.0));
+            for (i, row) in rows()?.enumerate() {
+                for (x, bytes) in row.chunks_exact(4).take(width).enumerate() {
+                    let pixel = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    let [red, green, blue, alpha] = masks.map(|mask| extract_channel(pixel, mask));
+                    image[[x, row_to_y(i)]] = Float3::new(red, green, blue);
+                    if let Some(opacity) = &mut opacity {
+                        opacity[[x, row_to_y(i)]] = alpha;
+                    }
+                }
+            }
+            alpha = opacity;
+        }
+        _ => unreachable!(),
+    }
+    Ok(DecodedImage { image, alpha })
+}
+
+/// Decode RLE8-compressed palette indices into `pixel_count` indices, row by row in the order of the file.
+/// Pixels the data skips keep index 0. The caller must check that the data is long enough for the pixel count.
+fn decode_rle8(data: &[u8], width: usize, height: usize, pixel_count: usize) -> Vec<u8> {
+    let mut indices = vec![0u8; pixel_count];
+    let (mut x, mut row) = (0, 0);
+    let mut put = |x: &mut usize, row: usize, index: u8| {
+        // Pixels beyond the end of the row are dropped
+        if *x < width && row < height {
+            indices[row * width + *x] = index;
+        }
+        *x += 1;
+    };
+
+    // The data is a sequence of byte pairs. A non-zero first byte repeats the second byte, while a zero first byte
+    // starts an escape sequence. Data ending without end-of-bitmap marker is accepted.
+    let mut i = 0;
+    while let Some(&[count, value]) = data.get(i..i + 2) {
+        i += 2;
+        match (count, value) {
+            // End of line
+            (0, 0) => (x, row) = (0, row + 1),
+            // End of bitmap
+            (0, 1) => break,
+            // Move right and down by the next two bytes
+            (0, 2) => {
+                let Some(&[dx, dy]) = data.get(i..i + 2) else { break };
+                i += 2;
+                x += dx as usize;
+                row += dy as usize;
+            }
+            // Copy the next `value` bytes, padded to an even number of bytes
+            (0, length) => {
+                let length = length as usize;
+                let Some(literal) = data.get(i..i + length) else { break };
+                for &index in literal {
+                    put(&mut x, row, index);
+                }
+                i += length + length % 2;
+            }
+            (count, index) => {
+                for _ in 0..count {
+                    put(&mut x, row, index);
+                }
+            }
+        }
+        if row >= height {
+            break;
+        }
+    }
+    indices
+}
+
+/// The channel selected by the mask from the pixel value, scaled to 0..1
+fn extract_channel(pixel: u32, mask: u32) -> f64 {
+    if mask == 0 {
+        return 0.0;
+    }
+    let shift = mask.trailing_zeros();
+    ((pixel & mask) >> shift) as f64 / (mask >> shift) as f64
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Result<i32> {
+    read_bytes(data, offset).map(i32::from_le_bytes)
+}
+
+/// The `N` bytes at the offset, failing if the headers end before them
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::InvalidImage("the BMP headers are truncated".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(red: u8, green: u8, blue: u8) -> Float3 {
+        Float3::new(red as f64, green as f64, blue as f64) / 255.0
+    }
+
+    /// A BMP file with an info header of the given size, whose fields beyond the first 40 bytes and any palette are
+    /// in `extra`, followed by the pixel data
+    fn bmp(width: i32, height: i32, bits: u16, compression: u32, header_size: u32, extra: &[u8], pixels: &[u8]) -> Vec<u8> {
+        let data_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE + extra.len() as u32;
+        let mut data = b"BM".to_vec();
+        data.extend((data_offset + pixels.len() as u32).to_le_bytes());
+        data.extend([0; 4]);
+        data.extend(data_offset.to_le_bytes());
+        data.extend(header_size.to_le_bytes());
+        data.extend(width.to_le_bytes());
+        data.extend(height.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        data.extend(bits.to_le_bytes());
+        data.extend(compression.to_le_bytes());
+        // Image size, resolution, used and important colors
+        data.extend([0; 20]);
+        data.extend(extra);
+        data.extend(pixels);
+        data
+    }
+
+    #[test]
+    fn reads_run_length_encoded_palette_indices() {
+        // Palette with black, red and green in the order BGR and an unused byte
+        let palette = [0, 0, 0, 0, 0, 0, 255, 0, 0, 255, 0, 0];
+        #[rustfmt::skip]
+        let pixels = [
+            // Bottom row: a run of four red pixels, then end of line
+            4, 1, 0, 0,
+            // A literal of three indices padded to an even length, a single red pixel and end of line
+            0, 3, 2, 0, 2, 0, 1, 1, 0, 0,
+            // Move one pixel to the right, two green pixels, and end of bitmap, leaving the other pixels at index 0
+            0, 2, 1, 0, 2, 2, 0, 1,
+        ];
+        let mut data = bmp(4, 3, 8, BI_RLE8, INFO_HEADER_SIZE, &palette, &pixels);
+        // Three colors in the palette
+        data[46] = 3;
+
+        let decoded = bmp_buffer_to_image(&data).unwrap();
+        let [black, red, green] = [rgb(0, 0, 0), rgb(255, 0, 0), rgb(0, 255, 0)];
+        let expected = [[black, green, green, black], [green, black, green, red], [red, red, red, red]];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                assert_eq!(decoded.image[[x, y]], *color, "pixel ({}, {})", x, y);
+            }
+        }
+        assert!(decoded.alpha.is_none());
+    }
+
+    #[test]
+    fn reads_channels_selected_by_bitfield_masks() {
+        // Red, green, blue and alpha masks of a BITMAPV3INFOHEADER, with red in the low byte
+        let mut masks = Vec::new();
+        for mask in [0x0000_00FFu32, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000] {
+            masks.extend(mask.to_le_bytes());
+        }
+        // Two top-down rows of a single pixel, each stored as RGBA
+        let pixels = [10, 20, 30, 255, 40, 50, 60, 0];
+        let data = bmp(1, -2, 32, BI_BITFIELDS, 56, &masks, &pixels);
+
+        let decoded = bmp_buffer_to_image(&data).unwrap();
+        assert_eq!(decoded.image[[0, 0]], rgb(10, 20, 30));
+        assert_eq!(decoded.image[[0, 1]], rgb(40, 50, 60));
+        let alpha = decoded.alpha.unwrap();
+        assert_eq!((alpha[[0, 0]], alpha[[0, 1]]), (1.0, 0.0));
+
+        // With only the color masks after a BITMAPINFOHEADER, channels of 5, 6 and 5 bits and no alpha
+        let mut masks = Vec::new();
+        for mask in [0xF800u32, 0x07E0, 0x001F] {
+            masks.extend(mask.to_le_bytes());
+        }
+        let pixels = 0xF81Fu32.to_le_bytes();
+        let decoded = bmp_buffer_to_image(&bmp(1, 1, 32, BI_BITFIELDS, INFO_HEADER_SIZE, &masks, &pixels)).unwrap();
+        assert_eq!(decoded.image[[0, 0]], Float3::new(1.0, 0.0, 1.0));
+        assert!(decoded.alpha.is_none());
+    }
+
+    #[test]
+    fn rejects_sizes_the_pixel_data_cannot_hold_before_allocating() {
+        let huge = i32::MAX;
+        for (bits, compression) in [(24, BI_RGB), (32, BI_RGB), (32, BI_BITFIELDS), (8, BI_RGB), (8, BI_RLE8)] {
+            let extra = [0u8; 12];
+            for (width, height) in [(huge, huge), (huge, -huge), (100_000, 100_000), (1, huge)] {
+                let data = bmp(width, height, bits, compression, INFO_HEADER_SIZE, &extra, &[0, 1]);
+                assert!(bmp_buffer_to_image(&data).is_err(), "{}x{} with {} bits", width, height, bits);
+            }
+        }
+
+        // An end-of-bitmap escape alone cannot describe a large image, but it can a small one
+        let end_of_bitmap = |size: i32| {
+            let mut data = bmp(size, size, 8, BI_RLE8, INFO_HEADER_SIZE, &[0; 4], &[0, 1]);
+            // A single color in the palette
+            data[46] = 1;
+            bmp_buffer_to_image(&data)
+        };
+        assert!(end_of_bitmap(1000).is_err());
+        assert!(end_of_bitmap(16).is_ok());
+    }
+
+    #[test]
+    fn written_files_read_back_in_both_row_orders() {
+        let mut image = ImageBuffer::new(3, 2);
+        let mut alpha = AlphaBuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                image[[x, y]] = rgb(x as u8 * 100, y as u8 * 200, 7);
+                alpha[[x, y]] = (x + y) as f64 / 255.0;
+            }
+        }
+        for row_order in [RowOrder::BottomUp, RowOrder::TopDown] {
+            let opaque = bmp_buffer_to_image(&image_to_bmp_buffer(&image, None, row_order).unwrap()).unwrap();
+            let transparent = bmp_buffer_to_image(&image_to_bmp_buffer(&image, Some(&alpha), row_order).unwrap()).unwrap();
+            assert!(opaque.alpha.is_none());
+            let decoded_alpha = transparent.alpha.unwrap();
+            for y in 0..2 {
+                for x in 0..3 {
+                    assert_eq!(opaque.image[[x, y]], image[[x, y]]);
+                    assert_eq!(transparent.image[[x, y]], image[[x, y]]);
+                    assert_eq!(decoded_alpha[[x, y]], alpha[[x, y]]);
+                }
+            }
+        }
+    }
+}
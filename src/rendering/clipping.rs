@@ -0,0 +1,121 @@
+use crate::vector_math::interpolation::Interpolate;
+use crate::vector_math::vector::Float3;
+
+/// A view-space vertex together with the attributes, which are interpolated when the triangle is clipped
+#[derive(Clone, Copy, Debug)]
+pub struct ClipVertex<A> {
+    pub position: Float3,
+    pub attributes: A,
+}
+
+impl<A: Interpolate> ClipVertex<A> {
+    pub fn new(position: Float3, attributes: A) -> Self {
+        Self { position, attributes }
+    }
+
+    fn lerp(a: &Self, b: &Self, t: f64) -> Self {
+        Self {
+            position: Float3::lerp(&a.position, &b.position, t),
+            attributes: A::lerp(&a.attributes, &b.attributes, t),
+        }
+    }
+}
+
+/// A plane in view space. Points with a non-negative signed distance are on the inside of the plane.
+pub struct Plane {
+    pub normal: Float3,
+    pub offset: f64,
+}
+
+impl Plane {
+    pub fn new(normal: Float3, offset: f64) -> Self {
+        Self { normal, offset }
+    }
+
+    /// Signed distance (scaled by the length of the normal) from the plane to `point`
+    pub fn signed_distance(&self, point: &Float3) -> f64 {
+        self.normal.dot(point) + self.offset
+    }
+}
+
+/// The visible volume of the camera, described by the planes bounding it in view space
+pub struct Frustum {
+    planes: Vec<Plane>,
+}
+
+impl Frustum {
+    /// Creates the frustum of a perspective camera looking down the positive z-axis.
+    ///
+    /// The half width and height are the extents of the view plane at a distance of 1 from the camera.
+    pub fn perspective(half_width: f64, half_height: f64, near: f64, far: f64) -> Self {
+        Self {
+            planes: vec![
+                // Near and far planes: near <= z <= far
+                Plane::new(Float3::new(0.0, 0.0, 1.0), -near),
+                Plane::new(Float3::new(0.0, 0.0, -1.0), far),
+                // Left and right planes: -z * half_width <= x <= z * half_width
+                Plane::new(Float3::new(1.0, 0.0, half_width), 0.0),
+                Plane::new(Float3::new(-1.0, 0.0, half_width), 0.0),
+                // Bottom and top planes: -z * half_height <= y <= z * half_height
+                Plane::new(Float3::new(0.0, 1.0, half_height), 0.0),
+                Plane::new(Float3::new(0.0, -1.0, half_height), 0.0),
+            ],
+        }
+    }
+
+    /// Creates the box-shaped frustum of an orthographic camera looking down the positive z-axis.
+    ///
+    /// The half width and height are the extents of the view plane.
+    pub fn orthographic(half_width: f64, half_height: f64, near: f64, far: f64) -> Self {
+        Self {
+            planes: vec![
+                // Near and far planes: near <= z <= far
+                Plane::new(Float3::new(0.0, 0.0, 1.0), -near),
+                Plane::new(Float3::new(0.0, 0.0, -1.0), far),
+                // Left and right planes: -half_width <= x <= half_width
+                Plane::new(Float3::new(1.0, 0.0, 0.0), half_width),
+                Plane::new(Float3::new(-1.0, 0.0, 0.0), half_width),
+                // Bottom and top planes: -half_height <= y <= half_height
+                Plane::new(Float3::new(0.0, 1.0, 0.0), half_height),
+                Plane::new(Float3::new(0.0, -1.0, 0.0), half_height),
+            ],
+        }
+    }
+
+    /// Clip the view-space triangle against the frustum.
+    ///
+    /// The clipped polygon is split into a triangle fan, so the result holds zero or more triangles with
+    /// the same winding as the input triangle. Vertex attributes are interpolated linearly in view space.
+    pub fn clip_triangle<A: Interpolate>(&self, triangle: [ClipVertex<A>; 3]) -> Vec<[ClipVertex<A>; 3]> {
+        let mut polygon = triangle.to_vec();
+        for plane in self.planes.iter() {
+            polygon = clip_polygon(&polygon, plane);
+            if polygon.len() < 3 {
+                return Vec::new();
+            }
+        }
+
+        (1..polygon.len() - 1).map(|i| [polygon[0], polygon[i], polygon[i + 1]]).collect()
+    }
+}
+
+/// Clip a convex polygon against a single plane (one step of the Sutherland-Hodgman algorithm)
+fn clip_polygon<A: Interpolate>(polygon: &[ClipVertex<A>], plane: &Plane) -> Vec<ClipVertex<A>> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let current_distance = plane.signed_distance(&current.position);
+        let next_distance = plane.signed_distance(&next.position);
+
+        if current_distance >= 0.0 {
+            clipped.push(*current);
+        }
+        // Add the intersection point, if the edge crosses the plane
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let t = current_distance / (current_distance - next_distance);
+            clipped.push(ClipVertex::lerp(current, next, t));
+        }
+    }
+    clipped
+}
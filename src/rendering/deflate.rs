@@ -0,0 +1,701 @@
+//! A zlib (RFC 1950) / deflate (RFC 1951) compressor, as needed for PNG files.
+//!
+//! The data is tokenized with LZ77 using hash chains, and each block is written with whichever of dynamic Huffman
+//! codes, the fixed Huffman codes or no compression gives the smallest output.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Size of the sliding window, the maximum distance of a match
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Number of earlier positions with the same hash that are tried when looking for a match
+const MAX_CHAIN: usize = 128;
+/// Stop looking for a longer match once one of this length is found
+const GOOD_MATCH: usize = 64;
+const HASH_BITS: u32 = 15;
+/// Number of LZ77 symbols collected before a block is written
+const BLOCK_SYMBOLS: usize = 1 << 16;
+
+const END_OF_BLOCK: usize = 256;
+const LITERAL_LENGTH_CODES: usize = 286;
+const DISTANCE_CODES: usize = 30;
+/// The fixed Huffman codes also assign codes to the two unused literal/length and distance symbols, which shifts the
+/// codes of the symbols after them
+const FIXED_LITERAL_LENGTH_CODES: usize = 288;
+const FIXED_DISTANCE_CODES: usize = 32;
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+/// Base lengths of the length codes 257..285, and the number of extra bits following them
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+/// Base distances of the distance codes 0..29, and the number of extra bits following them
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Compress the data into a zlib stream
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // Deflate with a 32K window (CMF), default compression level and a check value making CMF * 256 + FLG a
+    // multiple of 31 (FLG)
+    let cmf = 0x78u16;
+    let level = 2u16 << 6;
+    let check = 31 - (cmf * 256 + level) % 31;
+    writer.bytes.extend([cmf as u8, (level + check) as u8]);
+
+    deflate(data, &mut writer);
+
+    let mut bytes = writer.finish();
+    bytes.extend(adler32(data).to_be_bytes());
+    bytes
+}
+
+/// The Adler-32 checksum of zlib streams
+pub fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // The sums cannot overflow within 5552 bytes, so the modulo is only needed once per chunk
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    (b << 16) | a
+}
+
+/// An LZ77 symbol: a literal byte, or a match of `length` bytes starting `distance` bytes back
+#[derive(Clone, Copy, Debug)]
+enum Symbol {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+/// Write the data as a sequence of deflate blocks
+fn deflate(data: &[u8], writer: &mut BitWriter) {
+    let mut matcher = Matcher::new();
+    let mut symbols = Vec::with_capacity(BLOCK_SYMBOLS);
+    let mut block_start = 0;
+    let mut position = 0;
+
+    while position < data.len() {
+        let (length, distance) = matcher.find_match(data, position);
+        let advance = if length >= MIN_MATCH {
+            symbols.push(Symbol::Match { length: length as u16, distance: distance as u16 });
+            length
+        } else {
+            symbols.push(Symbol::Literal(data[position]));
+            1
+        };
+        for p in position..position + advance {
+            matcher.insert(data, p);
+        }
+        position += advance;
+
+        if symbols.len() >= BLOCK_SYMBOLS {
+            write_block(&symbols, &data[block_start..position], false, writer);
+            symbols.clear();
+            block_start = position;
+        }
+    }
+    // The last block is also written for empty data, since a stream needs at least one block
+    write_block(&symbols, &data[block_start..], true, writer);
+}
+
+/// Finds earlier occurrences of the upcoming bytes through chains of positions with the same hash
+struct Matcher {
+    /// Most recent position for each hash
+    head: Vec<Option<usize>>,
+    /// Previous position with the same hash, for every position in the window. Indexed by the position modulo the
+    /// window size, since older positions are out of reach anyway.
+    previous: Vec<Option<usize>>,
+}
+
+impl Matcher {
+    fn new() -> Self {
+        Self { head: vec![None; 1 << HASH_BITS], previous: vec![None; WINDOW_SIZE] }
+    }
+
+    fn hash(data: &[u8], position: usize) -> usize {
+        let value = (data[position] as u32) << 16 | (data[position + 1] as u32) << 8 | data[position + 2] as u32;
+        (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+    }
+
+    /// Make the position available for later matches
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if position + MIN_MATCH > data.len() {
+            return;
+        }
+        let hash = Self::hash(data, position);
+        self.previous[position % WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = Some(position);
+    }
+
+    /// The length and distance of the longest match found for the bytes at `position`
+    fn find_match(&self, data: &[u8], position: usize) -> (usize, usize) {
+        if position + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max_length = MAX_MATCH.min(data.len() - position);
+        let (mut best_length, mut best_distance) = (0, 0);
+
+        let mut candidate = self.head[Self::hash(data, position)];
+        for _ in 0..MAX_CHAIN {
+            let Some(start) = candidate else {
+                break;
+            };
+            let distance = position - start;
+            if distance > WINDOW_SIZE {
+                break;
+            }
+            // A candidate can only be longer if it also matches at the current best length
+            if data[start + best_length.min(max_length - 1)] == data[position + best_length.min(max_length - 1)] {
+                let length = data[start..start + max_length].iter()
+                                                            .zip(&data[position..position + max_length])
+                                                            .take_while(|(a, b)| a == b)
+                                                            .count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, distance);
+                    if length >= GOOD_MATCH.min(max_length) {
+                        break;
+                    }
+                }
+            }
+            candidate = self.previous[start % WINDOW_SIZE];
+        }
+        (best_length, best_distance)
+    }
+}
+
+/// Write one block of symbols with the smallest of the three block types. `data` holds the bytes encoded by the
+/// symbols, which are copied for stored blocks.
+fn write_block(symbols: &[Symbol], data: &[u8], is_final: bool, writer: &mut BitWriter) {
+    let mut literal_frequencies = [0u32; LITERAL_LENGTH_CODES];
+    let mut distance_frequencies = [0u32; DISTANCE_CODES];
+    for symbol in symbols {
+        match *symbol {
+            Symbol::Literal(byte) => literal_frequencies[byte as usize] += 1,
+            Symbol::Match { length, distance } => {
+                literal_frequencies[257 + length_code(length).0] += 1;
+                distance_frequencies[distance_code(distance).0] += 1;
+            }
+        }
+    }
+    literal_frequencies[END_OF_BLOCK] = 1;
+
+    let dynamic = DynamicHeader::new(&literal_frequencies, &distance_frequencies);
+    let (fixed_literals, fixed_distances) = fixed_code_lengths();
+
+    // Sizes in bits, excluding the 3 header bits shared by all block types
+    let symbols_size = |literal_lengths: &[u8], distance_lengths: &[u8]| -> usize {
+        let literals: usize = (0..LITERAL_LENGTH_CODES).map(|i| literal_frequencies[i] as usize * literal_lengths[i] as usize).sum();
+        let distances: usize = (0..DISTANCE_CODES).map(|i| distance_frequencies[i] as usize * distance_lengths[i] as usize).sum();
+        let extra_bits: usize = symbols.iter().map(|symbol| match *symbol {
+            Symbol::Literal(_) => 0,
+            Symbol::Match { length, distance } => (length_code(length).1 + distance_code(distance).1) as usize,
+        }).sum();
+        literals + distances + extra_bits
+    };
+    let dynamic_size = dynamic.get_size() + symbols_size(&dynamic.literal_lengths, &dynamic.distance_lengths);
+    let fixed_size = symbols_size(&fixed_literals, &fixed_distances);
+    // Stored blocks are byte aligned and hold at most 65535 bytes each
+    let stored_size = if data.len() <= u16::MAX as usize { 7 + 32 + 8 * data.len() } else { usize::MAX };
+
+    writer.write_bits(is_final as u32, 1);
+    if stored_size <= dynamic_size.min(fixed_size) {
+        writer.write_bits(0, 2);
+        writer.align_to_byte();
+        writer.bytes.extend((data.len() as u16).to_le_bytes());
+        writer.bytes.extend((!(data.len() as u16)).to_le_bytes());
+        writer.bytes.extend(data);
+    } else if fixed_size <= dynamic_size {
+        writer.write_bits(1, 2);
+        write_symbols(symbols, &canonical_codes(&fixed_literals), &canonical_codes(&fixed_distances), writer);
+    } else {
+        writer.write_bits(2, 2);
+        dynamic.write(writer);
+        write_symbols(symbols, &canonical_codes(&dynamic.literal_lengths), &canonical_codes(&dynamic.distance_lengths), writer);
+    }
+}
+
+/// Write the symbols followed by the end of block marker, using the given (code, length) pairs
+fn write_symbols(symbols: &[Symbol], literal_codes: &[(u16, u8)], distance_codes: &[(u16, u8)], writer: &mut BitWriter) {
+    for symbol in symbols {
+        match *symbol {
+            Symbol::Literal(byte) => writer.write_code(literal_codes[byte as usize]),
+            Symbol::Match { length, distance } => {
+                let (code, extra_bits) = length_code(length);
+                writer.write_code(literal_codes[257 + code]);
+                writer.write_bits((length - LENGTH_BASES[code]) as u32, extra_bits);
+
+                let (code, extra_bits) = distance_code(distance);
+                writer.write_code(distance_codes[code]);
+                writer.write_bits((distance - DISTANCE_BASES[code]) as u32, extra_bits);
+            }
+        }
+    }
+    writer.write_code(literal_codes[END_OF_BLOCK]);
+}
+
+/// The length code (0-based, i.e. minus 257) of a match length and its number of extra bits
+fn length_code(length: u16) -> (usize, u8) {
+    let code = LENGTH_BASES.partition_point(|&base| base <= length) - 1;
+    (code, LENGTH_EXTRA_BITS[code])
+}
+
+/// The distance code of a match distance and its number of extra bits
+fn distance_code(distance: u16) -> (usize, u8) {
+    let code = DISTANCE_BASES.partition_point(|&base| base <= distance) - 1;
+    (code, DISTANCE_EXTRA_BITS[code])
+}
+
+/// Code lengths of the fixed Huffman codes for literals/lengths and distances
+fn fixed_code_lengths() -> ([u8; FIXED_LITERAL_LENGTH_CODES], [u8; FIXED_DISTANCE_CODES]) {
+    let mut literals = [0u8; FIXED_LITERAL_LENGTH_CODES];
+    literals[0..144].fill(8);
+    literals[144..256].fill(9);
+    literals[256..280].fill(7);
+    literals[280..].fill(8);
+    (literals, [5; FIXED_DISTANCE_CODES])
+}
+
+/// The code lengths of a dynamic Huffman block, and their run-length encoded form for the block header
+struct DynamicHeader {
+    literal_lengths: Vec<u8>,
+    distance_lengths: Vec<u8>,
+    /// Number of literal/length and distance code lengths stored in the header
+    literal_count: usize,
+    distance_count: usize,
+    /// The code lengths as symbols of the code length alphabet, with the value of their extra bits
+    length_symbols: Vec<(u8, u8)>,
+    code_length_lengths: Vec<u8>,
+    /// Number of code length code lengths stored in the header
+    code_length_count: usize,
+}
+
+impl DynamicHeader {
+    fn new(literal_frequencies: &[u32], distance_frequencies: &[u32]) -> Self {
+        let literal_lengths = huffman_code_lengths(literal_frequencies, MAX_CODE_LENGTH);
+        let mut distance_lengths = huffman_code_lengths(distance_frequencies, MAX_CODE_LENGTH);
+        // Some decoders reject distance codes without any code, so give one code a length
+        if distance_lengths.iter().all(|&length| length == 0) {
+            distance_lengths[0] = 1;
+        }
+
+        let literal_count = 257.max(literal_lengths.iter().rposition(|&l| l > 0).unwrap_or(0) + 1);
+        let distance_count = 1.max(distance_lengths.iter().rposition(|&l| l > 0).unwrap_or(0) + 1);
+
+        let all_lengths: Vec<u8> = literal_lengths[..literal_count].iter()
+                                                                   .chain(&distance_lengths[..distance_count])
+                                                                   .copied()
+                                                                   .collect();
+        let length_symbols = run_length_encode(&all_lengths);
+
+        let mut code_length_frequencies = [0u32; 19];
+        for &(symbol, _) in length_symbols.iter() {
+            code_length_frequencies[symbol as usize] += 1;
+        }
+        let code_length_lengths = huffman_code_lengths(&code_length_frequencies, MAX_CODE_LENGTH_CODE_LENGTH);
+        let code_length_count = 4.max(CODE_LENGTH_ORDER.iter().rposition(|&i| code_length_lengths[i] > 0).unwrap_or(0) + 1);
+
+        Self {
+            literal_lengths,
+            distance_lengths,
+            literal_count,
+            distance_count,
+            length_symbols,
+            code_length_lengths,
+            code_length_count,
+        }
+    }
+
+    /// Size of the header in bits
+    fn get_size(&self) -> usize {
+        let symbols: usize = self.length_symbols.iter()
+                                                .map(|&(symbol, _)| self.code_length_lengths[symbol as usize] as usize + extra_bits_of(symbol) as usize)
+                                                .sum();
+        5 + 5 + 4 + 3 * self.code_length_count + symbols
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        writer.write_bits((self.literal_count - 257) as u32, 5);
+        writer.write_bits((self.distance_count - 1) as u32, 5);
+        writer.write_bits((self.code_length_count - 4) as u32, 4);
+        for &i in CODE_LENGTH_ORDER[..self.code_length_count].iter() {
+            writer.write_bits(self.code_length_lengths[i] as u32, 3);
+        }
+
+        let codes = canonical_codes(&self.code_length_lengths);
+        for &(symbol, extra) in self.length_symbols.iter() {
+            writer.write_code(codes[symbol as usize]);
+            writer.write_bits(extra as u32, extra_bits_of(symbol));
+        }
+    }
+}
+
+/// Number of extra bits following a symbol of the code length alphabet
+fn extra_bits_of(symbol: u8) -> u8 {
+    match symbol {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+/// Encode code lengths with the code length alphabet: 0..15 are lengths, 16 repeats the previous length 3-6 times,
+/// 17 repeats zero 3-10 times and 18 repeats zero 11-138 times
+fn run_length_encode(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut symbols = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let length = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == length).count();
+
+        if length == 0 && run >= 3 {
+            let count = run.min(138);
+            symbols.push(if count >= 11 { (18, (count - 11) as u8) } else { (17, (count - 3) as u8) });
+            i += count;
+        } else if length != 0 && run >= 4 {
+            // The first length is written explicitly, so it can be repeated
+            symbols.push((length, 0));
+            let count = (run - 1).min(6);
+            symbols.push((16, (count - 3) as u8));
+            i += 1 + count;
+        } else {
+            symbols.push((length, 0));
+            i += 1;
+        }
+    }
+    symbols
+}
+
+/// Lengths of a Huffman code for the frequencies, where no length exceeds `max_length`. Symbols that do not occur
+/// get length 0.
+fn huffman_code_lengths(frequencies: &[u32], max_length: u8) -> Vec<u8> {
+    let mut frequencies = frequencies.to_vec();
+    loop {
+        let lengths = unlimited_code_lengths(&frequencies);
+        if lengths.iter().all(|&length| length <= max_length) {
+            return lengths;
+        }
+        // Flatten the distribution until the tree is shallow enough. Used symbols stay used.
+        for frequency in frequencies.iter_mut().filter(|f| **f > 0) {
+            *frequency = (*frequency).div_ceil(2);
+        }
+    }
+}
+
+/// Lengths of an optimal Huffman code for the frequencies
+fn unlimited_code_lengths(frequencies: &[u32]) -> Vec<u8> {
+    let mut lengths = vec![0u8; frequencies.len()];
+    let used: Vec<usize> = (0..frequencies.len()).filter(|&i| frequencies[i] > 0).collect();
+    match used.len() {
+        0 => return lengths,
+        // A single symbol still needs a one bit code
+        1 => {
+            lengths[used[0]] = 1;
+            return lengths;
+        }
+        _ => (),
+    }
+
+    // Nodes are the used symbols followed by the internal nodes. Repeatedly merge the two lightest nodes.
+    let mut weights: Vec<u64> = used.iter().map(|&i| frequencies[i] as u64).collect();
+    let mut parents = vec![usize::MAX; used.len()];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> =
+        weights.iter().enumerate().map(|(node, &weight)| Reverse((weight, node))).collect();
+    while let (Some(Reverse((weight_a, a))), Some(Reverse((weight_b, b)))) = (heap.pop(), heap.pop()) {
+        let node = weights.len();
+        weights.push(weight_a + weight_b);
+        parents.push(usize::MAX);
+        parents[a] = node;
+        parents[b] = node;
+        heap.push(Reverse((weight_a + weight_b, node)));
+    }
+
+    // The depth of each leaf is the code length. Parents always come after their children.
+    let mut depths = vec![0u8; weights.len()];
+    for node in (0..weights.len() - 1).rev() {
+        depths[node] = depths[parents[node]] + 1;
+    }
+    for (leaf, &symbol) in used.iter().enumerate() {
+        lengths[symbol] = depths[leaf];
+    }
+    lengths
+}
+
+/// The canonical Huffman codes for the code lengths as (code, length) pairs
+fn canonical_codes(lengths: &[u8]) -> Vec<(u16, u8)> {
+    let max_length = lengths.iter().copied().max().unwrap_or(0) as usize;
+    let mut length_counts = vec![0u16; max_length + 1];
+    for &length in lengths.iter().filter(|&&l| l > 0) {
+        length_counts[length as usize] += 1;
+    }
+
+    // The first code of each length follows the codes of the shorter lengths
+    let mut next_code = vec![0u16; max_length + 1];
+    for length in 1..=max_length {
+        next_code[length] = (next_code[length - 1] + length_counts[length - 1]) << 1;
+    }
+
+    lengths.iter().map(|&length| {
+        if length == 0 {
+            return (0, 0);
+        }
+        let code = next_code[length as usize];
+        next_code[length as usize] += 1;
+        (code, length)
+    }).collect()
+}
+
+/// Packs bits into bytes starting at the least significant bit, as deflate requires
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), buffer: 0, bit_count: 0 }
+    }
+
+    /// Write the lowest `count` bits of the value, least significant bit first
+    fn write_bits(&mut self, value: u32, count: u8) {
+        self.buffer |= (value as u64 & ((1 << count) - 1)) << self.bit_count;
+        self.bit_count += count as u32;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which is stored starting with its most significant bit
+    fn write_code(&mut self, (code, length): (u16, u8)) {
+        let reversed = code.reverse_bits() >> (16 - length as u32);
+        self.write_bits(reversed as u32, length);
+    }
+
+    /// Pad with zero bits up to the next byte boundary
+    fn align_to_byte(&mut self) {
+        if self.bit_count > 0 {
+            self.write_bits(0, (8 - self.bit_count) as u8);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align_to_byte();
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Reads bits starting at the least significant bit of each byte
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn read_bits(&mut self, count: u8) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+                value |= (bit as u32) << i;
+                self.position += 1;
+            }
+            value
+        }
+
+        /// Decode a symbol bit by bit with the canonical codes of the code lengths
+        fn read_symbol(&mut self, lengths: &[u8]) -> usize {
+            let codes = canonical_codes(lengths);
+            let (mut code, mut length) = (0u16, 0u8);
+            loop {
+                code = code << 1 | self.read_bits(1) as u16;
+                length += 1;
+                if let Some(symbol) = codes.iter().position(|&c| c == (code, length)) {
+                    return symbol;
+                }
+                assert!(length < MAX_CODE_LENGTH, "invalid Huffman code");
+            }
+        }
+    }
+
+    /// A straightforward zlib decoder, sharing only the canonical code assignment and the RFC tables with the encoder
+    pub(crate) fn zlib_decompress(stream: &[u8]) -> Vec<u8> {
+        assert_eq!(stream[0] & 0x0F, 8, "compression method is not deflate");
+        assert_eq!((stream[0] as u16 * 256 + stream[1] as u16) % 31, 0, "header check failed");
+        let mut reader = BitReader { data: &stream[2..], position: 0 };
+        let mut output: Vec<u8> = Vec::new();
+        loop {
+            let is_final = reader.read_bits(1) == 1;
+            match reader.read_bits(2) {
+                0 => {
+                    let start = reader.position.div_ceil(8);
+                    let length = u16::from_le_bytes([reader.data[start], reader.data[start + 1]]) as usize;
+                    let complement = u16::from_le_bytes([reader.data[start + 2], reader.data[start + 3]]);
+                    assert_eq!(!(length as u16), complement);
+                    output.extend(&reader.data[start + 4..start + 4 + length]);
+                    reader.position = (start + 4 + length) * 8;
+                }
+                block_type @ (1 | 2) => {
+                    let (literal_lengths, distance_lengths) = if block_type == 1 {
+                        // The fixed code lengths as listed in RFC 1951, section 3.2.6
+                        let literals = [(144, 8), (112, 9), (24, 7), (8, 8)].iter()
+                                                                           .flat_map(|&(count, length)| std::iter::repeat_n(length, count));
+                        (literals.collect(), vec![5; 32])
+                    } else {
+                        read_dynamic_lengths(&mut reader)
+                    };
+                    loop {
+                        let symbol = reader.read_symbol(&literal_lengths);
+                        if symbol < 256 {
+                            output.push(symbol as u8);
+                            continue;
+                        }
+                        if symbol == END_OF_BLOCK {
+                            break;
+                        }
+                        let code = symbol - 257;
+                        let length = LENGTH_BASES[code] as usize + reader.read_bits(LENGTH_EXTRA_BITS[code]) as usize;
+                        let code = reader.read_symbol(&distance_lengths);
+                        let distance = DISTANCE_BASES[code] as usize + reader.read_bits(DISTANCE_EXTRA_BITS[code]) as usize;
+                        for _ in 0..length {
+                            output.push(output[output.len() - distance]);
+                        }
+                    }
+                }
+                _ => panic!("reserved block type"),
+            }
+            if is_final {
+                break;
+            }
+        }
+        let end = 2 + reader.position.div_ceil(8);
+        assert_eq!(stream[end..], adler32(&output).to_be_bytes(), "Adler-32 mismatch");
+        output
+    }
+
+    fn read_dynamic_lengths(reader: &mut BitReader) -> (Vec<u8>, Vec<u8>) {
+        let literal_count = reader.read_bits(5) as usize + 257;
+        let distance_count = reader.read_bits(5) as usize + 1;
+        let code_length_count = reader.read_bits(4) as usize + 4;
+        let mut code_length_lengths = [0u8; 19];
+        for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_lengths[symbol] = reader.read_bits(3) as u8;
+        }
+        let mut lengths = Vec::new();
+        while lengths.len() < literal_count + distance_count {
+            match reader.read_symbol(&code_length_lengths) {
+                length @ 0..=15 => lengths.push(length as u8),
+                16 => {
+                    let previous = *lengths.last().unwrap();
+                    let count = 3 + reader.read_bits(2) as usize;
+                    lengths.extend(std::iter::repeat_n(previous, count));
+                }
+                17 => {
+                    let count = 3 + reader.read_bits(3) as usize;
+                    lengths.extend(std::iter::repeat_n(0, count));
+                }
+                _ => {
+                    let count = 11 + reader.read_bits(7) as usize;
+                    lengths.extend(std::iter::repeat_n(0, count));
+                }
+            }
+        }
+        let distances = lengths.split_off(literal_count);
+        (lengths, distances)
+    }
+
+    /// Deterministic pseudo-random bytes
+    fn random_bytes(count: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..count).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        }).collect()
+    }
+
+    fn assert_round_trip(data: &[u8]) {
+        assert_eq!(zlib_decompress(&zlib_compress(data)), data);
+    }
+
+    #[test]
+    fn round_trips_empty_input() {
+        assert_round_trip(&[]);
+    }
+
+    #[test]
+    fn round_trips_single_bytes() {
+        for byte in 0..=255 {
+            assert_round_trip(&[byte]);
+        }
+    }
+
+    #[test]
+    fn round_trips_all_bytes() {
+        let data: Vec<u8> = (0..=255).collect();
+        assert_round_trip(&data);
+        let repeated: Vec<u8> = data.iter().cycle().take(5000).copied().collect();
+        assert_round_trip(&repeated);
+    }
+
+    #[test]
+    fn round_trips_short_random_inputs() {
+        // Short inputs are mostly written as fixed Huffman blocks
+        for length in 1..200 {
+            assert_round_trip(&random_bytes(length, length as u64));
+        }
+    }
+
+    #[test]
+    fn round_trips_large_inputs_over_several_blocks() {
+        let mut data = random_bytes(100_000, 7);
+        // Compressible runs and repetitions for long matches
+        data.extend(std::iter::repeat_n(42, 70_000));
+        data.extend((0..100_000).map(|i| (i % 251) as u8 ^ (i / 1000) as u8));
+        assert_round_trip(&data);
+    }
+
+    #[test]
+    fn fixed_codes_match_rfc_1951() {
+        let (literals, distances) = fixed_code_lengths();
+        let codes = canonical_codes(&literals);
+        assert_eq!(codes[0], (0b00110000, 8));
+        assert_eq!(codes[143], (0b10111111, 8));
+        assert_eq!(codes[144], (0b110010000, 9));
+        assert_eq!(codes[255], (0b111111111, 9));
+        assert_eq!(codes[256], (0, 7));
+        assert_eq!(codes[279], (0b0010111, 7));
+        assert_eq!(codes[280], (0b11000000, 8));
+        assert_eq!(codes[287], (0b11000111, 8));
+        assert_eq!(canonical_codes(&distances)[31], (31, 5));
+    }
+
+    #[test]
+    fn adler32_matches_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"a"), 0x00620062);
+        assert_eq!(adler32(b"abc"), 0x024d0127);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        // Long enough to need the modulo within the sums
+        assert_eq!(adler32(&[0xFF; 10_000]), 0xB623_EB2B);
+    }
+}
@@ -0,0 +1,319 @@
+use std::fs::{read, write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::image::{DecodedImage, ImageBuffer};
+use crate::vector_math::vector::Float3;
+
+/// Scanlines with a width in this range are run-length encoded
+const RLE_WIDTHS: std::ops::RangeInclusive<usize> = 8..=0x7FFF;
+/// Runs shorter than this are cheaper to store as part of a literal packet
+const MIN_RUN: usize = 4;
+
+/// Write the image to a Radiance RGBE file, keeping the full dynamic range of the colors
+pub fn write_hdr_file(image: &ImageBuffer, path: impl AsRef<Path>) -> Result<()> {
+    let hdr_buffer = image_to_hdr_buffer(image)?;
+    write(path.as_ref(), &hdr_buffer).map_err(|why| Error::from(why).in_file(path.as_ref()))
+}
+
+/// Encode the image as a Radiance RGBE file with run-length encoded scanlines. Each pixel stores an 8-bit mantissa per
+/// channel and a shared exponent, so colors keep about 1% relative precision at any brightness. Negative channels are
+/// stored as 0.
+pub fn image_to_hdr_buffer(image: &ImageBuffer) -> Result<Vec<u8>> {
+    if image.get_size() == 0 {
+        return Err(Error::EmptyImage);
+    }
+    let (width, height) = (image.get_width(), image.get_height());
+    // The resolution line stores the dimensions as signed 32-bit integers
+    if width > i32::MAX as usize || height > i32::MAX as usize {
+        return Err(Error::ImageTooLarge { width, height });
+    }
+
+    let mut buffer = Vec::new();
+    buffer.extend(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n");
+    // Rows from top to bottom, each from left to right
+    buffer.extend(format!("-Y {} +X {}\n", height, width).as_bytes());
+
+    let mut scanline = Vec::with_capacity(width);
+    for y in 0..height {
+        scanline.clear();
+        scanline.extend((0..width).map(|x| encode_rgbe(&image[[x, y]])));
+        if RLE_WIDTHS.contains(&width) {
+            buffer.extend([2, 2, (width >> 8) as u8, (width & 0xFF) as u8]);
+            // Each channel is encoded separately
+            for channel in 0..4 {
+                let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[channel]).collect();
+                encode_rle(&values, &mut buffer);
+            }
+        } else {
+            buffer.extend(scanline.iter().flatten());
+        }
+    }
+    Ok(buffer)
+}
+
+/// Read a Radiance RGBE file. See [`hdr_buffer_to_image`] for the supported variants.
+pub fn read_hdr_file(path: impl AsRef<Path>) -> Result<DecodedImage> {
+    let data = read(path.as_ref()).map_err(|why| Error::from(why).in_file(path.as_ref()))?;
+    hdr_buffer_to_image(&data).map_err(|why| why.in_file(path.as_ref()))
+}
+
+/// Decode a Radiance RGBE file with flat or run-length encoded scanlines, stored from top to bottom or from bottom to
+/// top. The colors are divided by the exposure recorded in the header. The files have no alpha channel.
+pub fn hdr_buffer_to_image(data: &[u8]) -> Result<DecodedImage> {
+    let invalid = |message: &str| Error::InvalidImage(message.to_string());
+    if !data.starts_with(b"#?") {
+        return Err(invalid("the Radiance signature is missing"));
+    }
+
+    // The header lines end with an empty line, followed by the resolution line
+    let mut lines = data.split(|&byte| byte == b'\n');
+    let mut position = 0;
+    let mut next_line = || {
+        let line = lines.next()?;
+        position += line.len() + 1;
+        std::str::from_utf8(line).ok().map(str::trim)
+    };
+    let mut exposure = 1.0;
+    loop {
+        let line = next_line().ok_or_else(|| invalid("the Radiance header is truncated"))?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") && format != "32-bit_rle_rgbe" {
+            return Err(Error::UnsupportedImage(format!("Radiance format {}", format)));
+        }
+        if let Some(value) = line.strip_prefix("EXPOSURE=") {
+            exposure *= value.trim().parse::<f64>().map_err(|_| invalid("the Radiance exposure is not a number"))?;
+        }
+    }
+    let resolution = next_line().ok_or_else(|| invalid("the Radiance resolution is missing"))?;
+    let (top_down, height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        [y_sign @ ("-Y" | "+Y"), height, "+X", width] => {
+            let parse = |value: &str| value.parse::<usize>().map_err(|_| invalid("the Radiance resolution is not a number"));
+            (y_sign == "-Y", parse(height)?, parse(width)?)
+        }
+        _ => return Err(Error::UnsupportedImage(format!("Radiance resolution \"{}\"", resolution))),
+    };
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyImage);
+    }
+    let too_large = || Error::ImageTooLarge { width, height };
+    width.checked_mul(height).ok_or_else(too_large)?;
+
+    // Check the size against the data before allocating the image, so a forged header cannot exhaust the memory. A
+    // run-length encoded scanline needs its 4-byte marker and a 2-byte run of up to 127 values per channel.
+    let mut pixels = data.get(position..).unwrap_or_default();
+    let flat_size = width.checked_mul(4).ok_or_else(too_large)?;
+    let min_scanline_size = if RLE_WIDTHS.contains(&width) { flat_size.min(4 + 8 * width.div_ceil(127)) } else { flat_size };
+    if height.checked_mul(min_scanline_size).ok_or_else(too_large)? > pixels.len() {
+        return Err(invalid("the Radiance pixel data is truncated"));
+    }
+    let mut image = ImageBuffer::new(width, height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for row in 0..height {
+        let is_rle = RLE_WIDTHS.contains(&width) && pixels.get(..4) == Some(&[2, 2, (width >> 8) as u8, (width & 0xFF) as u8]);
+        if is_rle {
+            pixels = &pixels[4..];
+            for channel in 0..4 {
+                pixels = decode_rle(pixels, &mut scanline, channel)?;
+            }
+        } else {
+            let flat = pixels.get(..4 * width).ok_or_else(|| invalid("the Radiance pixel data is truncated"))?;
+            for (rgbe, bytes) in scanline.iter_mut().zip(flat.chunks_exact(4)) {
+                rgbe.copy_from_slice(bytes);
+            }
+            pixels = &pixels[4 * width..];
+        }
+
+        let y = if top_down { row } else { height - 1 - row };
+        for (x, rgbe) in scanline.iter().enumerate() {
+            image[[x, y]] = decode_rgbe(rgbe) / exposure;
+        }
+    }
+    Ok(DecodedImage { image, alpha: None })
+}
+
+/// Convert a color to 8-bit mantissas sharing the exponent of the largest channel
+fn encode_rgbe(color: &Float3) -> [u8; 4] {
+    let max = color.r().max(color.g()).max(color.b());
+    if max.is_nan() || max < 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with the mantissa in 0.5..1. Larger exponents do not fit into a byte, so brighter
+    // colors saturate.
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let exponent = exponent.min(127);
+    let scale = 256.0 / 2f64.powi(exponent);
+    let channel = |value: f64| (value.max(0.0) * scale).min(255.0) as u8;
+    [channel(color.r()), channel(color.g()), channel(color.b()), (exponent + 128) as u8]
+}
+
+/// Convert shared-exponent mantissas to a color, reconstructing each channel at the center of its interval
+fn decode_rgbe(rgbe: &[u8; 4]) -> Float3 {
+    if rgbe[3] == 0 {
+        return Float3::zeros();
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Float3::new(rgbe[0] as f64 + 0.5, rgbe[1] as f64 + 0.5, rgbe[2] as f64 + 0.5) * scale
+}
+
+/// Append the values as packets: a run packet is the count plus 128 followed by the repeated value, a literal packet
+/// is the count followed by the values
+fn encode_rle(values: &[u8], buffer: &mut Vec<u8>) {
+    let mut start = 0;
+    while start < values.len() {
+        // Find the next run that is long enough to pay off
+        let mut run_start = start;
+        let mut run_length = 0;
+        while run_start < values.len() {
+            run_length = values[run_start..].iter().take(127).take_while(|&&value| value == values[run_start]).count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+        }
+
+        // Literal packets up to the run
+        for literal in values[start..run_start].chunks(128) {
+            buffer.push(literal.len() as u8);
+            buffer.extend(literal);
+        }
+        if run_start < values.len() {
+            buffer.extend([128 + run_length as u8, values[run_start]]);
+        }
+        start = run_start + run_length;
+    }
+}
+
+/// Decode the packets of one channel of a scanline, returning the remaining data
+fn decode_rle<'a>(mut data: &'a [u8], scanline: &mut [[u8; 4]], channel: usize) -> Result<&'a [u8]> {
+    let truncated = || Error::InvalidImage("the Radiance run-length encoded data is truncated".to_string());
+    let mut x = 0;
+    while x < scanline.len() {
+        let (&header, rest) = data.split_first().ok_or_else(truncated)?;
+        let (count, is_run) = if header > 128 { (header as usize - 128, true) } else { (header as usize, false) };
+        if count == 0 || x + count > scanline.len() {
+            return Err(Error::InvalidImage("a Radiance run-length packet exceeds the scanline".to_string()));
+        }
+        if is_run {
+            let &value = rest.first().ok_or_else(truncated)?;
+            for rgbe in &mut scanline[x..x + count] {
+                rgbe[channel] = value;
+            }
+            data = &rest[1..];
+        } else {
+            let values = rest.get(..count).ok_or_else(truncated)?;
+            for (rgbe, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                rgbe[channel] = value;
+            }
+            data = &rest[count..];
+        }
+        x += count;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image with smooth gradients, flat runs and a wide range of brightness
+    fn test_image(width: usize, height: usize) -> ImageBuffer {
+        let mut image = ImageBuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                image[[x, y]] = if x < width / 2 {
+                    Float3::new(0.5, 0.25, 2.0)
+                } else {
+                    Float3::new(x as f64 * 10.0, 1e-3 * (y + 1) as f64, 0.3 + y as f64)
+                };
+            }
+        }
+        image
+    }
+
+    fn assert_close(decoded: &ImageBuffer, image: &ImageBuffer) {
+        for y in 0..image.get_height() {
+            for x in 0..image.get_width() {
+                let (a, b) = (decoded[[x, y]], image[[x, y]]);
+                // The shared exponent gives every channel the precision of the largest one
+                let max = b.r().max(b.g()).max(b.b());
+                for (decoded, expected) in [(a.r(), b.r()), (a.g(), b.g()), (a.b(), b.b())] {
+                    assert!((decoded - expected).abs() <= max / 128.0, "{} != {} at ({}, {})", decoded, expected, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_flat_and_run_length_encoded_scanlines() {
+        // Widths below 8 are stored flat, the others run-length encoded
+        for (width, height) in [(1, 1), (7, 3), (8, 2), (33, 5), (300, 4)] {
+            let image = test_image(width, height);
+            let decoded = hdr_buffer_to_image(&image_to_hdr_buffer(&image).unwrap()).unwrap();
+            assert_eq!((decoded.image.get_width(), decoded.image.get_height()), (width, height));
+            assert_close(&decoded.image, &image);
+        }
+    }
+
+    #[test]
+    fn stores_black_and_negative_channels_as_zero() {
+        assert_eq!(encode_rgbe(&Float3::zeros()), [0; 4]);
+        assert_eq!(decode_rgbe(&[0; 4]), Float3::zeros());
+        assert_eq!(encode_rgbe(&Float3::new(-1.0, 0.5, 0.0)), [0, 128, 0, 128]);
+        assert_eq!(encode_rgbe(&Float3::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+    }
+
+    #[test]
+    fn run_length_packets_round_trip() {
+        let mut values: Vec<u8> = (0..300).map(|i| (i / 7) as u8).collect();
+        values.extend([9; 200]);
+        let mut buffer = Vec::new();
+        encode_rle(&values, &mut buffer);
+        let mut scanline = vec![[0u8; 4]; values.len()];
+        let rest = decode_rle(&buffer, &mut scanline, 2).unwrap();
+        assert!(rest.is_empty());
+        assert!(scanline.iter().zip(&values).all(|(rgbe, &value)| rgbe[2] == value));
+    }
+
+    #[test]
+    fn reads_bottom_up_files_with_exposure() {
+        let mut data = b"#?RGBE\nEXPOSURE=2\n\n+Y 2 +X 1\n".to_vec();
+        data.extend([128, 0, 0, 129, 0, 128, 0, 129]);
+        let image = hdr_buffer_to_image(&data).unwrap().image;
+        // The first scanline is the bottom row, and the colors are divided by the exposure
+        assert!((image[[0, 1]].r() - 0.5).abs() < 0.01 && image[[0, 1]].g() < 0.01);
+        assert!((image[[0, 0]].g() - 0.5).abs() < 0.01 && image[[0, 0]].r() < 0.01);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(matches!(hdr_buffer_to_image(b"P6\n"), Err(Error::InvalidImage(_))));
+        assert!(matches!(hdr_buffer_to_image(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n"), Err(Error::UnsupportedImage(_))));
+        assert!(matches!(hdr_buffer_to_image(b"#?RADIANCE\n\n+X 1 -Y 1\n"), Err(Error::UnsupportedImage(_))));
+        assert!(matches!(hdr_buffer_to_image(b"#?RADIANCE\n\n-Y 2 +X 2\n\x80\0\0\x81"), Err(Error::InvalidImage(_))));
+        assert!(matches!(image_to_hdr_buffer(&ImageBuffer::new(0, 0)), Err(Error::EmptyImage)));
+    }
+
+    #[test]
+    fn rejects_sizes_the_data_cannot_hold_before_allocating() {
+        let huge: [&[u8]; 4] = [
+            b"#?RADIANCE\n\n-Y 2147483647 +X 2147483647\n",
+            b"#?RADIANCE\n\n-Y 18446744073709551615 +X 1\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x02\x02\x86\xA0",
+            b"#?RADIANCE\n\n+Y 1 +X 4611686018427387904\n",
+        ];
+        for data in huge {
+            assert!(hdr_buffer_to_image(data).is_err(), "{:?}", String::from_utf8_lossy(data));
+        }
+
+        // The smallest run-length encoded scanline: the marker and a run per channel
+        let mut data = b"#?RADIANCE\n\n-Y 1 +X 100\n\x02\x02\x00\x64".to_vec();
+        data.extend([228, 0].repeat(4));
+        assert_eq!(hdr_buffer_to_image(&data).unwrap().image[[99, 0]], Float3::zeros());
+    }
+}
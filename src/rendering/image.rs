@@ -1,5 +1,7 @@
 use std::{f64, ops::{Index, IndexMut}};
 
+use crate::rendering::resampling::ResamplingFilter;
+use crate::vector_math::interpolation::Interpolate;
 use crate::vector_math::vector::Float3;
 
 pub trait Default<T> {
@@ -16,7 +18,16 @@ impl Default<f64> for f64 {
 
 pub type ImageBuffer = Buffer2D<Float3>;
 pub type DepthBuffer = Buffer2D<f64>;
+/// Opacity of each pixel, from 0 (transparent) to 1 (opaque)
+pub type AlphaBuffer = Buffer2D<f64>;
 
+/// An image read from a file, with an alpha plane if the file has an alpha channel
+pub struct DecodedImage {
+    pub image: ImageBuffer,
+    pub alpha: Option<AlphaBuffer>,
+}
+
+/// Grid of values stored row by row, with row 0 at the top
 pub struct Buffer2D<T: Default<T>> {
     buffer: Vec<T>,
     width: usize,
@@ -26,14 +37,18 @@ pub struct Buffer2D<T: Default<T>> {
 impl<T: Default<T>> Buffer2D<T> {
     pub fn new(width: usize, height: usize) -> Self {
         let size = width * height;
-        let mut buffer = Vec::new();
-        buffer.reserve(size);
+        let mut buffer = Vec::with_capacity(size);
         for _ in 0..size {
             buffer.push(T::get_default());
         }
         Self{ buffer, width, height }
     }
 
+    /// Creates a buffer with every element set to `value`
+    pub fn filled(width: usize, height: usize, value: T) -> Self where T: Clone {
+        Self { buffer: vec![value; width * height], width, height }
+    }
+
     pub fn get_size(&self) -> usize {
         self.width * self.height
     }
@@ -46,11 +61,66 @@ impl<T: Default<T>> Buffer2D<T> {
         self.height
     }
 
-    pub fn clear(&mut self) -> () {
+    pub fn clear(&mut self) {
         for i in 0..self.get_size() {
             self.buffer[i] = T::get_default();
         }
     }
+
+    /// Set every element to `value`
+    pub fn fill(&mut self, value: T) where T: Clone {
+        self.buffer.fill(value);
+    }
+
+    /// Copy of the `width` x `height` region with its top left corner at (x, y), which must lie inside the buffer
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self where T: Clone {
+        assert!(x + width <= self.width && y + height <= self.height, "the region exceeds the buffer");
+        let mut buffer = Vec::with_capacity(width * height);
+        for row in y..y + height {
+            let start = x + row * self.width;
+            buffer.extend_from_slice(&self.buffer[start..start + width]);
+        }
+        Self { buffer, width, height }
+    }
+
+    /// Copy `other` into the region with its top left corner at (x, y), which must lie inside the buffer
+    pub fn paste(&mut self, other: &Self, x: usize, y: usize) where T: Clone {
+        assert!(x + other.width <= self.width && y + other.height <= self.height, "the region exceeds the buffer");
+        for (row, source) in other.buffer.chunks_exact(other.width.max(1)).enumerate() {
+            let start = x + (y + row) * self.width;
+            self.buffer[start..start + other.width].clone_from_slice(source);
+        }
+    }
+
+    /// Scale the buffer to `width` x `height` elements, weighting the elements with the reconstruction filter. This
+    /// serves to downsample supersampled renders as well as to make thumbnails or enlarge images. An empty buffer
+    /// resamples to default elements.
+    pub fn resample(&self, width: usize, height: usize, filter: ResamplingFilter) -> Self where T: Interpolate {
+        if self.get_size() == 0 || width == 0 || height == 0 {
+            return Self::new(width, height);
+        }
+        let weighted_sum = |contributions: &[(usize, f64)], get: &dyn Fn(usize) -> T| {
+            let (first, rest) = contributions.split_first().expect("every element has a contribution");
+            rest.iter().fold(get(first.0) * first.1, |sum, &(i, weight)| sum + get(i) * weight)
+        };
+
+        // The filter is separable, so resample the rows first and then the columns
+        let columns = filter.get_contributions(self.width, width);
+        let mut rows_resampled = Self::new(width, self.height);
+        for y in 0..self.height {
+            for (x, contributions) in columns.iter().enumerate() {
+                rows_resampled[[x, y]] = weighted_sum(contributions, &|i| self[[i, y]]);
+            }
+        }
+        let rows = filter.get_contributions(self.height, height);
+        let mut resampled = Self::new(width, height);
+        for (y, contributions) in rows.iter().enumerate() {
+            for x in 0..width {
+                resampled[[x, y]] = weighted_sum(contributions, &|i| rows_resampled[[x, i]]);
+            }
+        }
+        resampled
+    }
 }
 
 impl<T: Default<T>> Index<[usize; 2]> for Buffer2D<T> {
@@ -65,3 +135,10 @@ impl<T: Default<T>> IndexMut<[usize; 2]> for Buffer2D<T> {
         &mut self.buffer[index[0] + index[1] * self.width]
     }
 }
+
+/// Map a color channel in 0..1 to the integers 0..=max, clamping values outside of the range and rounding to the
+/// nearest integer
+pub fn quantize(value: f64, max: f64) -> f64 {
+    // NaN becomes 0
+    (value.clamp(0.0, 1.0) * max).round().max(0.0)
+}
@@ -0,0 +1,83 @@
+use std::fs::read;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::bitmap::{bmp_buffer_to_image, write_bmp_file, RowOrder};
+use crate::rendering::hdr::{hdr_buffer_to_image, write_hdr_file};
+use crate::rendering::image::{AlphaBuffer, DecodedImage, ImageBuffer};
+use crate::rendering::pfm::{pfm_buffer_to_image, write_pfm_file};
+use crate::rendering::png::{write_png_file, BitDepth};
+use crate::rendering::ppm::ppm_buffer_to_image;
+use crate::rendering::tga::tga_buffer_to_image;
+
+/// File formats images can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp {
+        row_order: RowOrder,
+    },
+    Png {
+        bit_depth: BitDepth,
+    },
+    /// Radiance RGBE, keeping the dynamic range of the colors
+    Hdr,
+    /// Portable Float Map, storing the colors as 32-bit floats
+    Pfm,
+}
+
+impl Default for ImageFormat {
+    fn default() -> Self {
+        ImageFormat::Bmp { row_order: RowOrder::default() }
+    }
+}
+
+impl ImageFormat {
+    /// The format with the given name or file extension, e.g. "png", ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "bmp" => Some(ImageFormat::Bmp { row_order: RowOrder::default() }),
+            "png" => Some(ImageFormat::Png { bit_depth: BitDepth::default() }),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    /// The format matching the extension of the path
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        Self::from_name(path.as_ref().extension()?.to_str()?)
+    }
+
+    /// Whether the format can store an alpha channel
+    pub fn supports_alpha(&self) -> bool {
+        matches!(self, ImageFormat::Bmp { .. } | ImageFormat::Png { .. })
+    }
+}
+
+/// Write the image to a file in the given format. The alpha plane is dropped by formats without alpha channel.
+pub fn write_image_file(image: &ImageBuffer, alpha: Option<&AlphaBuffer>, format: ImageFormat, path: impl AsRef<Path>) -> Result<()> {
+    match format {
+        ImageFormat::Bmp { row_order } => write_bmp_file(image, alpha, row_order, path),
+        ImageFormat::Png { bit_depth } => write_png_file(image, alpha, bit_depth, path),
+        ImageFormat::Hdr => write_hdr_file(image, path),
+        ImageFormat::Pfm => write_pfm_file(image, path),
+    }
+}
+
+/// Read a BMP, PPM, PGM, Radiance HDR, PFM or TGA file. The format is recognized by the signature at the start of the
+/// file, except for TGA files, which have none and are recognized by the extension.
+pub fn read_image_file(path: impl AsRef<Path>) -> Result<DecodedImage> {
+    let path = path.as_ref();
+    let data = read(path).map_err(|why| Error::from(why).in_file(path))?;
+    let is_tga = path.extension().and_then(|extension| extension.to_str())
+                     .is_some_and(|extension| extension.eq_ignore_ascii_case("tga"));
+    let image = match data[..] {
+        [b'B', b'M', ..] => bmp_buffer_to_image(&data),
+        [b'P', b'2' | b'3' | b'5' | b'6', ..] => ppm_buffer_to_image(&data),
+        [b'P', b'F' | b'f' | b'd', ..] => pfm_buffer_to_image(&data),
+        [b'#', b'?', ..] => hdr_buffer_to_image(&data),
+        _ if is_tga => tga_buffer_to_image(&data),
+        _ => Err(Error::UnsupportedImage("unknown image format".to_string())),
+    };
+    image.map_err(|why| why.in_file(path))
+}
@@ -0,0 +1,395 @@
+use std::ops::{Add, Mul};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use crate::objects::{Material, Model};
+use crate::rendering::pipeline::{Fragment, FragmentShader, VertexOutput, VertexShader};
+use crate::rendering::texture::{Sampler, Texture};
+use crate::vector_math::matrix::{Mat3, Mat4};
+use crate::vector_math::vector::{Float2, Float3};
+
+/// A light source illuminating the scene
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    /// Light arriving from the same direction everywhere, e.g. sunlight
+    Directional {
+        /// Direction the light travels in
+        direction: Float3,
+        color: Float3,
+        intensity: f64,
+    },
+    /// Light emitted in all directions from a position, falling off with the squared distance
+    Point {
+        position: Float3,
+        color: Float3,
+        intensity: f64,
+    },
+    /// Point light restricted to a cone, fading out between the inner and outer cone angles
+    Spot {
+        position: Float3,
+        /// Direction of the center of the cone
+        direction: Float3,
+        color: Float3,
+        intensity: f64,
+        /// Half-angle of the fully lit cone in radians
+        inner_angle: f64,
+        /// Half-angle of the cone in radians, outside of which nothing is lit
+        outer_angle: f64,
+    },
+}
+
+impl Light {
+    /// The unit direction from `position` towards the light, and the light arriving at the position
+    pub fn illuminate(&self, position: &Float3) -> (Float3, Float3) {
+        match *self {
+            Light::Directional { direction, color, intensity } => (-direction.normalize(), color * intensity),
+            Light::Point { position: light_position, color, intensity } => {
+                let offset = light_position - position;
+                (offset.normalize(), color * (intensity / offset.length_squared()))
+            }
+            Light::Spot { position: light_position, direction, color, intensity, inner_angle, outer_angle } => {
+                let offset = light_position - position;
+                let to_light = offset.normalize();
+
+                // Smoothly fade out the light between the cosines of the inner and outer angle
+                let cos_angle = -to_light.dot(&direction.normalize());
+                let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+                let cone = ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-12)).clamp(0.0, 1.0);
+
+                (to_light, color * (intensity * cone / offset.length_squared()))
+            }
+        }
+    }
+}
+
+/// Where the lighting is evaluated. It is chosen for each `render3d` call through the `LightingShader` passed as vertex
+/// and fragment shader, since the pipeline itself knows nothing about lighting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShadingMode {
+    /// Once per triangle using the face normal
+    Flat,
+    /// At the vertices, interpolating the resulting colors across the triangle
+    Gouraud,
+    /// At every pixel, interpolating the vertex normals across the triangle
+    #[default]
+    Phong,
+}
+
+impl ShadingMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "flat" => Some(Self::Flat),
+            "gouraud" => Some(Self::Gouraud),
+            "phong" => Some(Self::Phong),
+            _ => None,
+        }
+    }
+}
+
+/// How strongly a surface reflects the different kinds of light
+#[derive(Clone, Copy, Debug)]
+pub struct Reflectance {
+    /// Scales the surface color under the ambient light
+    pub ambient: Float3,
+    /// Color of the specular highlights
+    pub specular: Float3,
+    /// Blinn-Phong exponent. Higher values give smaller, sharper highlights.
+    pub shininess: f64,
+}
+
+impl Add for Reflectance {
+    type Output = Reflectance;
+    fn add(self, rhs: Reflectance) -> Self::Output {
+        Reflectance {
+            ambient: self.ambient + rhs.ambient,
+            specular: self.specular + rhs.specular,
+            shininess: self.shininess + rhs.shininess,
+        }
+    }
+}
+
+impl Mul<f64> for Reflectance {
+    type Output = Reflectance;
+    fn mul(self, rhs: f64) -> Self::Output {
+        Reflectance {
+            ambient: self.ambient * rhs,
+            specular: self.specular * rhs,
+            shininess: self.shininess * rhs,
+        }
+    }
+}
+
+/// Varyings of the `LightingShader`
+#[derive(Clone, Copy, Debug)]
+pub struct LightingVaryings {
+    pub world_position: Float3,
+    pub normal: Float3,
+    /// The lit color for flat and Gouraud shading, and the unlit surface color for Phong shading
+    pub color: Float3,
+    pub uv: Float2,
+    pub reflectance: Reflectance,
+    /// Index of the material of the triangle in `Model::materials`. This is the same for all vertices of a triangle,
+    /// so it is passed on instead of interpolated.
+    pub material: Option<usize>,
+}
+
+impl Add for LightingVaryings {
+    type Output = LightingVaryings;
+    fn add(self, rhs: LightingVaryings) -> Self::Output {
+        LightingVaryings {
+            world_position: self.world_position + rhs.world_position,
+            normal: self.normal + rhs.normal,
+            color: self.color + rhs.color,
+            uv: self.uv + rhs.uv,
+            reflectance: self.reflectance + rhs.reflectance,
+            material: self.material.or(rhs.material),
+        }
+    }
+}
+
+impl Mul<f64> for LightingVaryings {
+    type Output = LightingVaryings;
+    fn mul(self, rhs: f64) -> Self::Output {
+        LightingVaryings {
+            world_position: self.world_position * rhs,
+            normal: self.normal * rhs,
+            color: self.color * rhs,
+            uv: self.uv * rhs,
+            reflectance: self.reflectance * rhs,
+            material: self.material,
+        }
+    }
+}
+
+/// Lights the triangles, colored by `Model::triangle_colors`, with Lambert diffuse and Blinn-Phong specular
+/// reflection. The reflectance comes from the material of each triangle, falling back to the values of the shader
+/// for triangles without a material. The colors are multiplied by the diffuse texture of the material at the texture
+/// coordinates of the model, or by the texture of the shader for triangles without one. Materials with illumination
+/// model 0 are not lit, and materials with illumination model 1 have no specular highlights.
+///
+/// Uses the vertex normals of the model for Gouraud and Phong shading, or the face normals if the model has none.
+/// The materials and the transform are taken from the model at the start of each `render3d` call.
+pub struct LightingShader {
+    pub lights: Vec<Light>,
+    pub shading_mode: ShadingMode,
+    /// Light reaching every surface regardless of the light sources
+    pub ambient: Float3,
+    /// Color of the specular highlights of triangles without a material
+    pub specular: Float3,
+    /// Blinn-Phong exponent of triangles without a material
+    pub shininess: f64,
+    /// World position of the viewer, needed for the specular highlights
+    pub view_position: Float3,
+    /// Texture modulating the surface colors of triangles without a diffuse texture in their material
+    pub texture: Option<Texture>,
+    pub sampler: Sampler,
+    /// What the current `render3d` call shares between all vertices and fragments
+    draw: RwLock<DrawState>,
+}
+
+/// The state of a `LightingShader` for the model being rendered
+struct DrawState {
+    model_matrix: Mat4,
+    normal_matrix: Mat3,
+    materials: Vec<Material>,
+}
+
+impl LightingShader {
+    pub fn new(lights: Vec<Light>, shading_mode: ShadingMode, view_position: Float3) -> Self {
+        Self {
+            lights,
+            shading_mode,
+            ambient: Float3::splat(0.1),
+            specular: Float3::splat(0.5),
+            shininess: 32.0,
+            view_position,
+            texture: None,
+            sampler: Sampler::default(),
+            draw: RwLock::new(DrawState { model_matrix: Mat4::identity(), normal_matrix: Mat3::identity(), materials: Vec::new() }),
+        }
+    }
+
+    /// The state of the current draw. It is only written between draws, so a poisoned lock still holds a whole state.
+    fn get_draw_state(&self) -> RwLockReadGuard<'_, DrawState> {
+        self.draw.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The reflectance of the triangle with the given index
+    pub fn get_reflectance(&self, model: &Model, triangle: usize) -> Reflectance {
+        match model.get_material(triangle) {
+            Some(material) => Reflectance {
+                ambient: material.ambient,
+                specular: if material.has_highlights() { material.specular } else { Float3::zeros() },
+                shininess: material.shininess,
+            },
+            None => Reflectance { ambient: Float3::splat(1.0), specular: self.specular, shininess: self.shininess },
+        }
+    }
+
+    /// The color of a surface with the given (unit) normal, color and reflectance, as lit by all the lights
+    pub fn shade_surface(&self, position: &Float3, normal: &Float3, color: &Float3, reflectance: &Reflectance) -> Float3 {
+        let to_viewer = (self.view_position - position).normalize();
+
+        let mut result = self.ambient * reflectance.ambient * color;
+        for light in self.lights.iter() {
+            let (to_light, radiance) = light.illuminate(position);
+            let lambert = normal.dot(&to_light);
+            if lambert <= 0.0 {
+                continue;
+            }
+            let halfway = (to_light + to_viewer).normalize();
+            let specular = normal.dot(&halfway).max(0.0).powf(reflectance.shininess);
+            result += (color * lambert + reflectance.specular * specular) * radiance;
+        }
+        result
+    }
+}
+
+impl VertexShader for LightingShader {
+    type Varyings = LightingVaryings;
+
+    fn begin_draw(&self, model: &Model) {
+        let model_matrix = model.transform.get_model_matrix();
+        // A singular transform flattens the model, so there are no meaningful normals to preserve
+        let normal_matrix = model_matrix.get_normal_matrix().unwrap_or_else(|| model_matrix.get_mat3());
+        let mut draw = self.draw.write().unwrap_or_else(PoisonError::into_inner);
+        *draw = DrawState { model_matrix, normal_matrix, materials: model.materials.clone() };
+    }
+
+    fn shade(&self, model: &Model, index: usize) -> VertexOutput<LightingVaryings> {
+        let (model_matrix, normal_matrix) = {
+            let draw = self.get_draw_state();
+            (draw.model_matrix, draw.normal_matrix)
+        };
+        let world_position = model_matrix.transform_point(&model.vertices[index]);
+        let surface_color = model.triangle_colors[index / 3];
+        let reflectance = self.get_reflectance(model, index / 3);
+        let material = model.triangle_materials.get(index / 3).copied().flatten();
+        let is_lit = model.get_material(index / 3).is_none_or(Material::is_lit);
+        // Models without vertex normals are shaded with the face normals
+        let vertex_normal = model.normals.get(index).copied().unwrap_or_else(|| model.get_face_normal(index));
+
+        let (normal, color) = match self.shading_mode {
+            _ if !is_lit => ((normal_matrix * vertex_normal).normalize(), surface_color),
+            ShadingMode::Flat => {
+                // Light the whole triangle at its center, so all three vertices get the same color
+                let first = index - index % 3;
+                let center = (model.vertices[first] + model.vertices[first + 1] + model.vertices[first + 2]) / 3.0;
+                let normal = (normal_matrix * model.get_face_normal(index)).normalize();
+                (normal, self.shade_surface(&model_matrix.transform_point(&center), &normal, &surface_color, &reflectance))
+            }
+            ShadingMode::Gouraud => {
+                let normal = (normal_matrix * vertex_normal).normalize();
+                (normal, self.shade_surface(&world_position, &normal, &surface_color, &reflectance))
+            }
+            ShadingMode::Phong => ((normal_matrix * vertex_normal).normalize(), surface_color),
+        };
+
+        let uv = model.uvs.get(index).copied().unwrap_or(Float2::zeros());
+        VertexOutput {
+            position: world_position,
+            varyings: LightingVaryings { world_position, normal, color, uv, reflectance, material },
+        }
+    }
+}
+
+impl FragmentShader<LightingVaryings> for LightingShader {
+    fn shade(&self, fragment: &Fragment<LightingVaryings>) -> Float3 {
+        let varyings = &fragment.varyings;
+        // Only triangles with a material need the state of the draw
+        let draw = varyings.material.map(|index| (index, self.get_draw_state()));
+        let material = draw.as_ref().and_then(|(index, draw)| draw.materials.get(*index));
+        let texture = material.and_then(|material| material.diffuse_texture.as_deref()).or(self.texture.as_ref());
+        let texture_color = match texture {
+            Some(texture) => texture.sample_grad(&varyings.uv, &fragment.ddx.uv, &fragment.ddy.uv, &self.sampler),
+            None => Float3::splat(1.0),
+        };
+        let is_lit = material.is_none_or(Material::is_lit);
+        match self.shading_mode {
+            // The interpolated normal is shorter than unit length, so it must be normalized again
+            ShadingMode::Phong if is_lit => {
+                let normal = varyings.normal.normalize();
+                let color = varyings.color * texture_color;
+                self.shade_surface(&varyings.world_position, &normal, &color, &varyings.reflectance)
+            }
+            // The lighting is already done, or the surface is not lit, so modulate the color
+            _ => varyings.color * texture_color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::rendering::image::ImageBuffer;
+    use crate::rendering::transforms::Transform;
+
+    /// A gray triangle facing the light, with the material of the given index
+    fn model(materials: Vec<Material>, material: Option<usize>) -> Model {
+        Model {
+            vertices: vec![Float3::new(0.0, 1.0, 0.0), Float3::new(1.0, -1.0, 0.0), Float3::new(-1.0, -1.0, 0.0)],
+            normals: vec![Float3::new(0.0, 0.0, -1.0); 3],
+            uvs: vec![Float2::new(0.5, 0.5); 3],
+            triangle_colors: vec![Float3::splat(0.5)],
+            materials,
+            triangle_materials: vec![material],
+            triangle_smoothing_groups: Vec::new(),
+            transform: Transform::empty(),
+        }
+    }
+
+    fn shader(shading_mode: ShadingMode) -> LightingShader {
+        let light = Light::Directional { direction: Float3::new(0.0, 0.0, 1.0), color: Float3::splat(1.0), intensity: 1.0 };
+        let mut shader = LightingShader::new(vec![light], shading_mode, Float3::new(0.0, 0.0, -5.0));
+        shader.ambient = Float3::zeros();
+        shader
+    }
+
+    /// Shade the first vertex of the model, and then a fragment at that vertex
+    fn shade(shading_mode: ShadingMode, model: &Model) -> Float3 {
+        shade_with(&shader(shading_mode), model)
+    }
+
+    fn shade_with(shader: &LightingShader, model: &Model) -> Float3 {
+        shader.begin_draw(model);
+        let varyings = VertexShader::shade(shader, model, 0).varyings;
+        let zero = varyings * 0.0;
+        let fragment = Fragment { position: Float2::zeros(), depth: 1.0, varyings, ddx: zero, ddy: zero };
+        FragmentShader::shade(shader, &fragment)
+    }
+
+    #[test]
+    fn materials_choose_the_texture_and_illumination_model() {
+        let mut shiny = Material::new("shiny");
+        shiny.specular = Float3::splat(1.0);
+        let mut matte = shiny.clone();
+        matte.illumination_model = 1;
+        let mut unlit = Material::new("unlit");
+        unlit.illumination_model = 0;
+        unlit.diffuse_texture = Some(Arc::new(Texture::new(ImageBuffer::filled(1, 1, Float3::new(1.0, 0.5, 0.0)))));
+
+        for shading_mode in [ShadingMode::Flat, ShadingMode::Gouraud, ShadingMode::Phong] {
+            assert_eq!(shade(shading_mode, &model(vec![shiny.clone()], Some(0))), Float3::splat(1.5));
+            assert_eq!(shade(shading_mode, &model(vec![matte.clone()], Some(0))), Float3::splat(0.5));
+            // Without lighting, the color is only multiplied by the texture
+            assert_eq!(shade(shading_mode, &model(vec![unlit.clone()], Some(0))), Float3::new(0.5, 0.25, 0.0));
+        }
+    }
+
+    #[test]
+    fn each_draw_takes_the_materials_and_transform_of_its_model() {
+        let mut unlit = Material::new("unlit");
+        unlit.illumination_model = 0;
+        unlit.diffuse_texture = Some(Arc::new(Texture::new(ImageBuffer::filled(1, 1, Float3::new(1.0, 0.5, 0.0)))));
+        let mut turned = model(Vec::new(), None);
+        turned.transform = Transform::new(0.0, std::f64::consts::PI, Float3::zeros());
+
+        // The same shader renders models with different materials and transforms one after another
+        let shader = shader(ShadingMode::Phong);
+        assert_eq!(shade_with(&shader, &model(vec![unlit], Some(0))), Float3::new(0.5, 0.25, 0.0));
+        assert_eq!(shade_with(&shader, &model(Vec::new(), None)), shade(ShadingMode::Phong, &model(Vec::new(), None)));
+        // Turned around, the triangle faces away from the light
+        assert_eq!(shade_with(&shader, &turned), Float3::zeros());
+    }
+}
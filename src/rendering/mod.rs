@@ -1,13 +1,31 @@
 pub mod bitmap;
+pub mod clipping;
+mod deflate;
+pub mod hdr;
 pub mod image;
+pub mod image_format;
+pub mod lighting;
+pub mod multisampling;
+pub mod pfm;
 pub mod pipeline;
+pub mod png;
+pub mod ppm;
+pub mod resampling;
+pub mod shaders;
+pub mod texture;
+pub mod tga;
 pub mod transforms;
 
-use crate::rendering::image::{ImageBuffer, DepthBuffer};
+use crate::rendering::image::{AlphaBuffer, ImageBuffer, DepthBuffer};
+use crate::rendering::multisampling::{SampleBuffers, SampleCount};
+use crate::vector_math::vector::Float3;
 
 pub struct RenderTarget {
     pub image_buffer: ImageBuffer,
     pub depth_buffer: DepthBuffer,
+    /// The samples of a multisampled render target. After each draw call, the pipeline resolves the samples of the
+    /// pixels the call may have drawn to into the image and depth buffer.
+    pub samples: Option<SampleBuffers>,
 }
 
 impl RenderTarget {
@@ -15,9 +33,21 @@ impl RenderTarget {
         Self {
             image_buffer: ImageBuffer::new(width, height),
             depth_buffer: DepthBuffer::new(width, height),
+            samples: None,
         }
     }
 
+    /// Create a render target that tests the coverage and depth of triangles at `sample_count` points per pixel, but
+    /// shades each pixel only once per triangle
+    pub fn new_multisampled(width: usize, height: usize, sample_count: SampleCount) -> Self {
+        let samples = (sample_count != SampleCount::One).then(|| SampleBuffers::new(width, height, sample_count));
+        Self { samples, ..Self::new(width, height) }
+    }
+
+    pub fn get_sample_count(&self) -> SampleCount {
+        self.samples.as_ref().map_or(SampleCount::One, |samples| samples.sample_count)
+    }
+
     pub fn get_size(&self) -> usize {
         self.image_buffer.get_size()
     }
@@ -30,8 +60,56 @@ impl RenderTarget {
         self.image_buffer.get_height()
     }
 
-    pub fn clear(&mut self) -> () {
+    pub fn clear(&mut self) {
         self.image_buffer.clear();
         self.depth_buffer.clear();
+        if let Some(samples) = &mut self.samples {
+            samples.colors.clear();
+            samples.depths.clear();
+        }
+    }
+
+    /// Opacity from the coverage of the image: the fraction of the samples of each pixel where a triangle was drawn
+    pub fn get_alpha(&self) -> AlphaBuffer {
+        let mut alpha = AlphaBuffer::filled(self.get_width(), self.get_height(), 0.0);
+        let count = self.get_sample_count().get_count();
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                alpha[[x, y]] = match &self.samples {
+                    Some(samples) => {
+                        let covered = (0..count).filter(|&s| samples.depths[samples.get_index(x, y, s)].is_finite()).count();
+                        covered as f64 / count as f64
+                    }
+                    None if self.depth_buffer[[x, y]].is_finite() => 1.0,
+                    None => 0.0,
+                };
+            }
+        }
+        alpha
+    }
+
+    /// Clear the depth buffer and fill the image with the background color
+    pub fn clear_to(&mut self, background: &Float3) {
+        self.image_buffer.fill(*background);
+        self.depth_buffer.clear();
+        if let Some(samples) = &mut self.samples {
+            samples.colors.fill(*background);
+            samples.depths.clear();
+        }
+    }
+
+    /// Average the samples of a multisampled render target into the image buffer, and keep the closest sample depth
+    /// of each pixel in the depth buffer
+    pub fn resolve(&mut self) {
+        if let Some(samples) = &self.samples {
+            samples.resolve(&mut self.image_buffer, &mut self.depth_buffer);
+        }
+    }
+
+    /// Like [`Self::resolve`], but only for the pixels in the region starting at (x, y)
+    pub fn resolve_region(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if let Some(samples) = &self.samples {
+            samples.resolve_region(&mut self.image_buffer, &mut self.depth_buffer, x, y, width, height);
+        }
     }
 }
\ No newline at end of file
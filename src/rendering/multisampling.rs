@@ -0,0 +1,111 @@
+use crate::rendering::image::{DepthBuffer, ImageBuffer};
+
+/// Sample offsets are given in units of 1/`SAMPLE_GRID` pixels
+pub const SAMPLE_GRID: i64 = 16;
+
+/// Number of coverage and depth samples per pixel of a render target
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SampleCount {
+    /// A single sample at the pixel center, without multisampling
+    #[default]
+    One,
+    Two,
+    Four,
+    Eight,
+    Sixteen,
+}
+
+impl SampleCount {
+    pub fn from_count(count: usize) -> Option<Self> {
+        match count {
+            1 => Some(Self::One),
+            2 => Some(Self::Two),
+            4 => Some(Self::Four),
+            8 => Some(Self::Eight),
+            16 => Some(Self::Sixteen),
+            _ => None,
+        }
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.get_offsets().len()
+    }
+
+    /// Offsets of the samples from the pixel center in 1/[`SAMPLE_GRID`] pixels, with the y-axis pointing down. These
+    /// are the standard sample patterns of Direct3D and Vulkan, which spread the samples over distinct rows and columns.
+    pub fn get_offsets(&self) -> &'static [[i8; 2]] {
+        match self {
+            Self::One => &[[0, 0]],
+            Self::Two => &[[4, 4], [-4, -4]],
+            Self::Four => &[[-2, -6], [6, -2], [-6, 2], [2, 6]],
+            Self::Eight => &[[1, -3], [-1, 3], [5, 1], [-3, -5], [-5, 5], [-7, -1], [3, 7], [7, -7]],
+            Self::Sixteen => &[
+                [1, 1], [-1, -3], [-3, 2], [4, -1], [-5, -2], [2, 5], [5, 3], [3, -5],
+                [-2, 6], [0, -7], [-4, -6], [-6, 4], [-8, 0], [7, -4], [6, 7], [-7, -8],
+            ],
+        }
+    }
+}
+
+/// The colors and depths of the samples of a multisampled render target. The samples of a pixel are stored next to
+/// each other along x, so sample `s` of pixel (x, y) is at `[x * count + s, y]`.
+pub struct SampleBuffers {
+    pub sample_count: SampleCount,
+    pub colors: ImageBuffer,
+    pub depths: DepthBuffer,
+}
+
+impl SampleBuffers {
+    pub fn new(width: usize, height: usize, sample_count: SampleCount) -> Self {
+        let samples_width = width * sample_count.get_count();
+        Self {
+            sample_count,
+            colors: ImageBuffer::new(samples_width, height),
+            depths: DepthBuffer::new(samples_width, height),
+        }
+    }
+
+    /// The index of the sample of the pixel in the buffers
+    pub fn get_index(&self, x: usize, y: usize, sample: usize) -> [usize; 2] {
+        [x * self.sample_count.get_count() + sample, y]
+    }
+
+    /// Copy of the samples of the pixels in the region
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        let count = self.sample_count.get_count();
+        Self {
+            sample_count: self.sample_count,
+            colors: self.colors.crop(x * count, y, width * count, height),
+            depths: self.depths.crop(x * count, y, width * count, height),
+        }
+    }
+
+    /// Copy the samples of the other buffers to the pixels starting at (x, y)
+    pub fn paste(&mut self, other: &Self, x: usize, y: usize) {
+        assert_eq!(self.sample_count, other.sample_count, "the sample counts differ");
+        let count = self.sample_count.get_count();
+        self.colors.paste(&other.colors, x * count, y);
+        self.depths.paste(&other.depths, x * count, y);
+    }
+
+    /// Average the sample colors of each pixel into the image, and keep the closest sample depth of each pixel
+    pub fn resolve(&self, image: &mut ImageBuffer, depth: &mut DepthBuffer) {
+        self.resolve_region(image, depth, 0, 0, image.get_width(), image.get_height());
+    }
+
+    /// Like [`Self::resolve`], but only for the pixels in the region starting at (x, y)
+    pub fn resolve_region(&self, image: &mut ImageBuffer, depth: &mut DepthBuffer, x: usize, y: usize, width: usize, height: usize) {
+        let count = self.sample_count.get_count();
+        for y in y..y + height {
+            for x in x..x + width {
+                let (mut color_sum, mut closest) = (self.colors[[x * count, y]], self.depths[[x * count, y]]);
+                for sample in 1..count {
+                    color_sum += self.colors[[x * count + sample, y]];
+                    closest = closest.min(self.depths[[x * count + sample, y]]);
+                }
+                image[[x, y]] = color_sum / count as f64;
+                depth[[x, y]] = closest;
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Portable Float Map files: a text header with the magic number, the size and a scale whose sign gives the byte
+//! order, followed by the samples of the rows from bottom to top. "PF" files hold three 32-bit float channels and "Pf"
+//! files one.
+//!
+//! Depth buffers are written as "Pd" files, a variant of "Pf" with 64-bit float samples, so the depths round-trip
+//! exactly. Tools that only know the standard variants can't read them.
+
+use std::fs::{read, write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::image::{DecodedImage, DepthBuffer, ImageBuffer};
+use crate::vector_math::vector::Float3;
+
+/// Write the image to a Portable Float Map file with three channels
+pub fn write_pfm_file(image: &ImageBuffer, path: impl AsRef<Path>) -> Result<()> {
+    let pfm_buffer = image_to_pfm_buffer(image)?;
+    write(path.as_ref(), &pfm_buffer).map_err(|why| Error::from(why).in_file(path.as_ref()))
+}
+
+/// Write the depth buffer to a 64-bit grayscale Portable Float Map file
+pub fn write_depth_pfm_file(depth: &DepthBuffer, path: impl AsRef<Path>) -> Result<()> {
+    let pfm_buffer = depth_to_pfm_buffer(depth)?;
+    write(path.as_ref(), &pfm_buffer).map_err(|why| Error::from(why).in_file(path.as_ref()))
+}
+
+/// Encode the image as a Portable Float Map file, storing the colors as 32-bit floats without clamping
+pub fn image_to_pfm_buffer(image: &ImageBuffer) -> Result<Vec<u8>> {
+    encode(image.get_width(), image.get_height(), "PF", 3 * 4, |x, y, samples| {
+        let color = image[[x, y]];
+        samples.extend([color.r(), color.g(), color.b()].map(|value| (value as f32).to_le_bytes()).iter().flatten());
+    })
+}
+
+/// Encode the depth buffer as a grayscale Portable Float Map file with 64-bit samples ("Pd"). Every depth round-trips
+/// exactly, including the infinite depth of empty pixels.
+pub fn depth_to_pfm_buffer(depth: &DepthBuffer) -> Result<Vec<u8>> {
+    encode(depth.get_width(), depth.get_height(), "Pd", 8, |x, y, samples| {
+        samples.extend(depth[[x, y]].to_le_bytes());
+    })
+}
+
+/// Read a Portable Float Map file. See [`pfm_buffer_to_image`] for the supported variants.
+pub fn read_pfm_file(path: impl AsRef<Path>) -> Result<DecodedImage> {
+    let data = read(path.as_ref()).map_err(|why| Error::from(why).in_file(path.as_ref()))?;
+    pfm_buffer_to_image(&data).map_err(|why| why.in_file(path.as_ref()))
+}
+
+/// Read a grayscale Portable Float Map file with 32-bit or 64-bit samples as a depth buffer
+pub fn read_depth_pfm_file(path: impl AsRef<Path>) -> Result<DepthBuffer> {
+    let data = read(path.as_ref()).map_err(|why| Error::from(why).in_file(path.as_ref()))?;
+    pfm_buffer_to_depth(&data).map_err(|why| why.in_file(path.as_ref()))
+}
+
+/// Decode a color (PF) or grayscale (Pf, or Pd with 64-bit samples) Portable Float Map file in either byte order.
+/// Gray values are copied to all three color channels. The files have no alpha channel.
+pub fn pfm_buffer_to_image(data: &[u8]) -> Result<DecodedImage> {
+    let (width, height, channels, samples) = decode(data)?;
+    let mut image = ImageBuffer::new(width, height);
+    for (i, pixel) in samples.chunks_exact(channels).enumerate() {
+        image[[i % width, i / width]] = match *pixel {
+            [gray] => Float3::splat(gray),
+            [red, green, blue] => Float3::new(red, green, blue),
+            _ => unreachable!(),
+        };
+    }
+    Ok(DecodedImage { image, alpha: None })
+}
+
+/// Decode a grayscale (Pf, or Pd with 64-bit samples) Portable Float Map file in either byte order as a depth buffer
+pub fn pfm_buffer_to_depth(data: &[u8]) -> Result<DepthBuffer> {
+    let (width, height, channels, samples) = decode(data)?;
+    if channels != 1 {
+        return Err(Error::InvalidImage("a depth buffer needs a grayscale PFM file".to_string()));
+    }
+    let mut depth = DepthBuffer::new(width, height);
+    for (i, &value) in samples.iter().enumerate() {
+        depth[[i % width, i / width]] = value;
+    }
+    Ok(depth)
+}
+
+/// Write the header and the `pixel_size` bytes that `write_pixel` appends for each pixel. The rows are stored from
+/// bottom to top, in little-endian byte order.
+fn encode(
+    width: usize,
+    height: usize,
+    magic: &str,
+    pixel_size: usize,
+    mut write_pixel: impl FnMut(usize, usize, &mut Vec<u8>),
+) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyImage);
+    }
+    let too_large = || Error::ImageTooLarge { width, height };
+    let size = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(pixel_size)).ok_or_else(too_large)?;
+
+    // A negative scale marks little-endian samples
+    let mut buffer = format!("{}\n{} {}\n-1.0\n", magic, width, height).into_bytes();
+    buffer.reserve(size);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            write_pixel(x, y, &mut buffer);
+        }
+    }
+    Ok(buffer)
+}
+
+/// The width, height, number of channels and the samples of the file, with the rows from top to bottom
+fn decode(data: &[u8]) -> Result<(usize, usize, usize, Vec<f64>)> {
+    let invalid = |message: &str| Error::InvalidImage(message.to_string());
+    // The header consists of three lines: the magic number, the size and the scale
+    let mut lines = data.splitn(4, |&byte| byte == b'\n');
+    let mut next_line = || lines.next().and_then(|line| std::str::from_utf8(line).ok()).map(str::trim);
+    let (channels, sample_size) = match next_line() {
+        Some("PF") => (3, 4),
+        Some("Pf") => (1, 4),
+        Some("Pd") => (1, 8),
+        _ => return Err(invalid("the PFM signature is missing")),
+    };
+    let (width, height) = next_line().and_then(|size| {
+        let (width, height) = size.split_once(char::is_whitespace)?;
+        Some((width.trim().parse::<usize>().ok()?, height.trim().parse::<usize>().ok()?))
+    }).ok_or_else(|| invalid("the PFM size is malformed"))?;
+    let scale: f64 = next_line().and_then(|scale| scale.parse().ok())
+                                .filter(|&scale: &f64| scale != 0.0 && scale.is_finite())
+                                .ok_or_else(|| invalid("the PFM scale is malformed"))?;
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyImage);
+    }
+
+    let sample_count = width.checked_mul(height)
+                            .and_then(|pixels| pixels.checked_mul(channels))
+                            .ok_or(Error::ImageTooLarge { width, height })?;
+    let bytes = lines.next()
+                     .and_then(|samples| samples.get(..sample_count.checked_mul(sample_size)?))
+                     .ok_or_else(|| invalid("the PFM pixel data is truncated"))?;
+    let samples: Vec<f64> = bytes.chunks_exact(sample_size).map(|bytes| match *bytes {
+        [a, b, c, d] if scale < 0.0 => f32::from_le_bytes([a, b, c, d]) as f64,
+        [a, b, c, d] => f32::from_be_bytes([a, b, c, d]) as f64,
+        _ if scale < 0.0 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        _ => f64::from_be_bytes(bytes.try_into().unwrap()),
+    }).collect();
+
+    // Flip the rows, which are stored from bottom to top
+    let row_size = width * channels;
+    Ok((width, height, channels, samples.chunks_exact(row_size).rev().flatten().copied().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image() -> ImageBuffer {
+        let mut image = ImageBuffer::new(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                image[[x, y]] = Float3::new(x as f64 * 0.25, -(y as f64) * 1.5, 1e6 + x as f64);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn images_round_trip_in_single_precision() {
+        let image = test_image();
+        let decoded = pfm_buffer_to_image(&image_to_pfm_buffer(&image).unwrap()).unwrap();
+        assert!(decoded.alpha.is_none());
+        for y in 0..3 {
+            for x in 0..5 {
+                let expected = image[[x, y]];
+                let expected = Float3::new(expected.r() as f32 as f64, expected.g() as f32 as f64, expected.b() as f32 as f64);
+                assert_eq!(decoded.image[[x, y]], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn depth_round_trips_exactly() {
+        let mut depth = DepthBuffer::new(4, 3);
+        depth[[0, 0]] = 0.1;
+        depth[[1, 0]] = 1.0 / 3.0;
+        depth[[2, 1]] = 12345.678901234567;
+        depth[[3, 2]] = f64::MIN_POSITIVE;
+        let buffer = depth_to_pfm_buffer(&depth).unwrap();
+        assert!(buffer.starts_with(b"Pd\n4 3\n-1.0\n"));
+        let decoded = pfm_buffer_to_depth(&buffer).unwrap();
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(decoded[[x, y]].to_bits(), depth[[x, y]].to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn reads_big_endian_rows_from_bottom_to_top() {
+        let mut data = b"Pf\n2 2\n1.0\n".to_vec();
+        for value in [3.0f32, 4.0, 1.0, 2.0] {
+            data.extend(value.to_be_bytes());
+        }
+        let depth = pfm_buffer_to_depth(&data).unwrap();
+        assert_eq!([depth[[0, 0]], depth[[1, 0]], depth[[0, 1]], depth[[1, 1]]], [1.0, 2.0, 3.0, 4.0]);
+        let image = pfm_buffer_to_image(&data).unwrap().image;
+        assert_eq!(image[[1, 1]], Float3::splat(4.0));
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(matches!(pfm_buffer_to_image(b"P6\n1 1\n-1.0\n"), Err(Error::InvalidImage(_))));
+        assert!(matches!(pfm_buffer_to_image(b"PF\n1\n-1.0\n"), Err(Error::InvalidImage(_))));
+        assert!(matches!(pfm_buffer_to_image(b"PF\n1 1\n0\n"), Err(Error::InvalidImage(_))));
+        assert!(matches!(pfm_buffer_to_image(b"PF\n2 2\n-1.0\n\0\0\0\0"), Err(Error::InvalidImage(_))));
+        assert!(matches!(pfm_buffer_to_image(b"PF\n0 2\n-1.0\n"), Err(Error::EmptyImage)));
+        let color = image_to_pfm_buffer(&test_image()).unwrap();
+        assert!(matches!(pfm_buffer_to_depth(&color), Err(Error::InvalidImage(_))));
+    }
+}
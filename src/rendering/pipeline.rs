@@ -1,87 +1,680 @@
-use std::f64::consts::PI;
+use std::iter::zip;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
+use crate::error::{Error, Result};
 use crate::objects::Model;
-use crate::rendering::transforms::vertex_to_screen;
+use crate::rendering::clipping::ClipVertex;
+use crate::rendering::multisampling::SAMPLE_GRID;
+use crate::rendering::shaders::FlatColorShader;
+use crate::rendering::transforms::Camera;
 use crate::rendering::RenderTarget;
-use crate::vector_math::{vector::*, triangle::*};
+use crate::vector_math::{interpolation::Interpolate, vector::*, triangle::*};
 
+/// Which triangles to discard, based on whether they face towards or away from the camera
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullMode {
+    /// Draw all triangles
+    None,
+    /// Discard triangles facing away from the camera
+    #[default]
+    Back,
+    /// Discard triangles facing towards the camera
+    Front,
+}
+
+/// Winding order of the vertices of a triangle as seen in the output image, where the y-axis points down
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Winding {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Side length of the square screen tiles that triangles are binned into for multithreaded rasterization. It is even,
+/// so no 2x2 pixel quad straddles two tiles.
+const TILE_SIZE: usize = 64;
+
+/// Number representation of the screen-space triangle setup
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precision {
+    /// Floating-point vertex positions and edge functions
+    #[default]
+    Float,
+    /// Vertex positions snapped to 1/256 pixel with integer edge functions, so which pixels a triangle covers is
+    /// exact and does not depend on float rounding. Triangles reaching beyond `FIXED_POINT_RANGE` pixels fall back
+    /// to floating point.
+    FixedPoint,
+}
+
+/// Settings controlling how triangles are rasterized
+#[derive(Clone, Debug, Default)]
+pub struct RenderSettings {
+    pub cull_mode: CullMode,
+    /// The winding order of front-facing triangles in screen space, i.e. as they appear in the output image. This is
+    /// the same for `render2d` and `render3d`.
+    pub front_face: Winding,
+    /// Number of threads rasterizing screen tiles in parallel, or 0 for one thread per available core.
+    /// The output is the same for any number of threads.
+    pub threads: usize,
+    pub precision: Precision,
+}
+
+impl RenderSettings {
+    /// The number of threads to rasterize with, resolving 0 to the available parallelism
+    pub fn get_thread_count(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
+}
+
+/// Output of the vertex shader for a single vertex
+pub struct VertexOutput<V> {
+    /// World-space position of the vertex
+    pub position: Float3,
+    /// Values that are interpolated across the triangle and handed to the fragment shader
+    pub varyings: V,
+}
+
+/// Computes the world-space position and the varyings of the vertices of a model
+pub trait VertexShader {
+    type Varyings: Interpolate;
+
+    /// Called once at the start of each `render3d` call, before any vertex of the model is shaded. Shaders can keep
+    /// what is the same for all vertices of the draw, e.g. the transformation matrices of the model.
+    fn begin_draw(&self, _model: &Model) {}
+
+    /// Shade the vertex at `index` in the vertex list of the model
+    fn shade(&self, model: &Model, index: usize) -> VertexOutput<Self::Varyings>;
+}
+
+/// A pixel covered by a triangle
+pub struct Fragment<V> {
+    /// Pixel coordinates of the pixel center
+    pub position: Float2,
+    /// Linear view-space depth
+    pub depth: f64,
+    /// The varyings of the vertex shader, interpolated at the pixel
+    pub varyings: V,
+    /// Change of the varyings from one pixel to the next along x, e.g. for texture filtering
+    pub ddx: V,
+    /// Change of the varyings from one pixel to the next along y
+    pub ddy: V,
+}
 
-/// Render triangles to an image buffer using rasterization
-pub fn render2d(vertices: &Vec<Float2>, colors: &Vec<Float3>, render_target: &mut RenderTarget) -> () {
-    // For now, zero-size images cause a program panic
+/// Computes the color of the pixels covered by a triangle
+pub trait FragmentShader<V> {
+    fn shade(&self, fragment: &Fragment<V>) -> Float3;
+}
+
+/// Render triangles to an image buffer using rasterization.
+///
+/// The vertices are pixel coordinates in screen space, with the origin in the top-left corner and the y-axis pointing
+/// down, so their winding order is judged as they appear in the image.
+pub fn render2d(
+    vertices: &[Float2],
+    colors: &[Float3],
+    render_target: &mut RenderTarget,
+    settings: &RenderSettings,
+) -> Result<()> {
     if render_target.get_size() == 0 {
-        panic!("Image has no size!")
+        return Err(Error::EmptyImage);
+    }
+    if !vertices.len().is_multiple_of(3) || colors.len() != vertices.len() / 3 {
+        return Err(Error::InvalidModel(format!("{} vertices do not fit {} triangle colors", vertices.len(), colors.len())));
     }
 
     // Loop over the triangles
+    let mut screen_triangles = Vec::with_capacity(colors.len());
     for i in (0..vertices.len()).step_by(3) {
-        // Extract vertices
-        let a = &vertices[i];
-        let b = &vertices[i + 1];
-        let c = &vertices[i + 2];
+        // Without a perspective divide, every vertex has w = 1 and the attributes are interpolated linearly
+        let color = colors[i / 3];
+        let a = ScreenVertex::new(Float3::new(vertices[i].x, vertices[i].y, 0.0), 1.0, color);
+        let b = ScreenVertex::new(Float3::new(vertices[i + 1].x, vertices[i + 1].y, 0.0), 1.0, color);
+        let c = ScreenVertex::new(Float3::new(vertices[i + 2].x, vertices[i + 2].y, 0.0), 1.0, color);
 
-        let a3d = Float3::new(a.x, a.y, 0.0);
-        let b3d = Float3::new(b.x, b.y, 0.0);
-        let c3d = Float3::new(c.x, c.y, 0.0);
-
-        let bbox = determine_bounding_box(&a3d, &b3d, &c3d, render_target.get_width(), render_target.get_height());
-        paint_in_triangle(&a3d, &b3d, &c3d, bbox, colors[i / 3], render_target);
-        
+        screen_triangles.extend(orient_triangle([a, b, c], settings));
     }
+    rasterize(&screen_triangles, &FlatColorShader, render_target, settings);
+    Ok(())
 }
 
-/// Render 3D triangles to an image buffer using rasterization
-/// 
-/// fov must be in degrees
-pub fn render3d(object: &Model, render_target: &mut RenderTarget, fov: f64) -> () {
+/// Render 3D triangles to an image buffer using rasterization, as seen from the camera with the given shaders
+pub fn render3d<VS, FS>(
+    object: &Model,
+    vertex_shader: &VS,
+    fragment_shader: &FS,
+    render_target: &mut RenderTarget,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> Result<()>
+where
+    VS: VertexShader,
+    VS::Varyings: Sync,
+    FS: FragmentShader<VS::Varyings> + Sync,
+{
     if render_target.get_size() == 0 {
-        panic!("Image has no size!")
+        return Err(Error::EmptyImage);
     }
+    object.validate()?;
 
-    let fov_rad = fov / 180.0 * PI;
     let image_size = Float2::new(render_target.get_width() as f64, render_target.get_height() as f64);
+    let frustum = camera.get_frustum(&image_size);
+    vertex_shader.begin_draw(object);
 
-    // Loop over the triangles
+    // Vertex stage: transform, clip and project the triangles, keeping the ones that are not culled
+    let mut screen_triangles = Vec::new();
     for i in (0..object.vertices.len()).step_by(3) {
-        let a = vertex_to_screen(&object.vertices[i + 0], &object.transform, &image_size, fov_rad);
-        let b = vertex_to_screen(&object.vertices[i + 1], &object.transform, &image_size, fov_rad);
-        let c = vertex_to_screen(&object.vertices[i + 2], &object.transform, &image_size, fov_rad);
+        let triangle = [i, i + 1, i + 2].map(|v| {
+            let vertex = vertex_shader.shade(object, v);
+            ClipVertex::new(camera.world_to_view(&vertex.position), vertex.varyings)
+        });
+
+        // Clip before the perspective divide, so no vertex behind the camera is projected.
+        // Clipping may split the triangle into several triangles.
+        for clipped in frustum.clip_triangle(triangle) {
+            let screen_triangle = clipped.map(|v| {
+                let position = camera.view_to_screen(&v.position, &image_size);
+                ScreenVertex::new(position, 1.0 / camera.get_w(&v.position), v.attributes)
+            });
+            screen_triangles.extend(orient_triangle(screen_triangle, settings));
+        }
+    }
+    rasterize(&screen_triangles, fragment_shader, render_target, settings);
+    Ok(())
+}
+
+/// A vertex projected onto the screen
+#[derive(Clone, Copy, Debug)]
+struct ScreenVertex<A> {
+    /// Pixel coordinates in x and y, and the view-space depth in z
+    position: Float3,
+    /// Reciprocal of the w-coordinate divided out by the projection (1/depth for perspective projections)
+    inv_w: f64,
+    /// Vertex attributes interpolated across the triangle
+    attributes: A,
+}
+
+impl<A: Interpolate> ScreenVertex<A> {
+    fn new(position: Float3, inv_w: f64, attributes: A) -> Self {
+        Self { position, inv_w, attributes }
+    }
+}
+
+/// Paint the oriented screen-space triangles in order. With more than one thread, the triangles are binned into screen
+/// tiles, which are painted in parallel. Each tile paints its triangles in the original order and every pixel belongs
+/// to exactly one tile, so the result is the same as painting all triangles on one thread.
+fn rasterize<A, FS>(triangles: &[[ScreenVertex<A>; 3]], fragment_shader: &FS, render_target: &mut RenderTarget, settings: &RenderSettings)
+where
+    A: Interpolate + Sync,
+    FS: FragmentShader<A> + Sync,
+{
+    let (width, height) = (render_target.get_width(), render_target.get_height());
+    // With multisampling, the bounding boxes also contain the pixels whose samples but not centers are covered
+    let margin = if render_target.samples.is_some() { 0.5 } else { 0.0 };
+    // Triangles between the pixel centers have no bounding box
+    let bounding_boxes: Vec<Option<BBox>> = triangles.iter().map(|triangle| {
+        let [a, b, c] = triangle.map(|v| v.position);
+        determine_bounding_box(&a, &b, &c, margin, width, height)
+    }).collect();
+
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tiles_y = height.div_ceil(TILE_SIZE);
+    let threads = settings.get_thread_count().min(tiles_x * tiles_y);
+    if threads <= 1 {
+        for (triangle, bbox) in zip(triangles, &bounding_boxes) {
+            if let Some(bbox) = bbox {
+                paint_in_triangle(triangle, *bbox, fragment_shader, render_target, [0, 0], settings.precision);
+            }
+        }
+    } else {
+        paint_tiles(triangles, &bounding_boxes, fragment_shader, render_target, settings.precision, threads);
+    }
+
+    // Only the pixels inside the bounding boxes may have changed, so only they need to be resolved. The region is the
+    // same for any number of threads, so the pixels around the triangles are left alike.
+    if let Some(painted) = bounding_boxes.into_iter().flatten().reduce(|painted, bbox| painted.union(&bbox)) {
+        render_target.resolve_region(painted.min_x, painted.min_y, painted.get_width(), painted.get_height());
+    }
+}
+
+/// Bin the triangles into screen tiles and paint the tiles on the given number of threads
+fn paint_tiles<A, FS>(
+    triangles: &[[ScreenVertex<A>; 3]],
+    bounding_boxes: &[Option<BBox>],
+    fragment_shader: &FS,
+    render_target: &mut RenderTarget,
+    precision: Precision,
+    threads: usize,
+) where
+    A: Interpolate + Sync,
+    FS: FragmentShader<A> + Sync,
+{
+    let (width, height) = (render_target.get_width(), render_target.get_height());
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tiles_y = height.div_ceil(TILE_SIZE);
+
+    // Bin the triangles into the tiles their bounding boxes overlap, keeping their order
+    let mut bins = vec![Vec::new(); tiles_x * tiles_y];
+    for (i, bbox) in bounding_boxes.iter().enumerate() {
+        let Some(bbox) = bbox else {
+            continue;
+        };
+        for tile_y in bbox.min_y / TILE_SIZE..=bbox.max_y / TILE_SIZE {
+            for tile_x in bbox.min_x / TILE_SIZE..=bbox.max_x / TILE_SIZE {
+                bins[tile_x + tile_y * tiles_x].push(i);
+            }
+        }
+    }
+    let get_tile = |index: usize| {
+        let (min_x, min_y) = (index % tiles_x * TILE_SIZE, index / tiles_x * TILE_SIZE);
+        BBox { min_x, min_y, max_x: (min_x + TILE_SIZE).min(width) - 1, max_y: (min_y + TILE_SIZE).min(height) - 1 }
+    };
+
+    // The threads take the next unpainted tile until all are done, painting into copies of the tiles
+    let next_tile = AtomicUsize::new(0);
+    let source: &RenderTarget = render_target;
+    let painted_tiles: Vec<(BBox, RenderTarget)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+            let mut painted_tiles = Vec::new();
+            loop {
+                let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                let Some(bin) = bins.get(index) else {
+                    break;
+                };
+                // Tiles without triangles are left as they are
+                if bin.is_empty() {
+                    continue;
+                }
+                let tile = get_tile(index);
+                let mut tile_target = crop_render_target(source, &tile);
+                for &i in bin {
+                    let Some(bbox) = &bounding_boxes[i] else {
+                        continue;
+                    };
+                    let bbox = bbox.intersect(&tile);
+                    let origin = [tile.min_x, tile.min_y];
+                    paint_in_triangle(&triangles[i], bbox, fragment_shader, &mut tile_target, origin, precision);
+                }
+                painted_tiles.push((tile, tile_target));
+            }
+            painted_tiles
+        })).collect();
+        workers.into_iter()
+               .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+               .collect()
+    });
+
+    for (tile, tile_target) in painted_tiles {
+        render_target.image_buffer.paste(&tile_target.image_buffer, tile.min_x, tile.min_y);
+        render_target.depth_buffer.paste(&tile_target.depth_buffer, tile.min_x, tile.min_y);
+        if let (Some(samples), Some(tile_samples)) = (&mut render_target.samples, &tile_target.samples) {
+            samples.paste(tile_samples, tile.min_x, tile.min_y);
+        }
+    }
+}
+
+/// Copy of the region of the render target covered by the tile
+fn crop_render_target(render_target: &RenderTarget, tile: &BBox) -> RenderTarget {
+    let (width, height) = (tile.get_width(), tile.get_height());
+    RenderTarget {
+        image_buffer: render_target.image_buffer.crop(tile.min_x, tile.min_y, width, height),
+        depth_buffer: render_target.depth_buffer.crop(tile.min_x, tile.min_y, width, height),
+        samples: render_target.samples.as_ref().map(|samples| samples.crop(tile.min_x, tile.min_y, width, height)),
+    }
+}
+
+/// Determine the winding of the screen-space triangle once, and return its vertices in clockwise order. With
+/// fixed-point precision, the vertices are snapped to the subpixel grid first and the winding is determined exactly.
+/// Returns `None` if the triangle is culled or has no area.
+fn orient_triangle<A: Interpolate>(mut triangle: [ScreenVertex<A>; 3], settings: &RenderSettings) -> Option<[ScreenVertex<A>; 3]> {
+    let fixed = match settings.precision {
+        Precision::Float => None,
+        Precision::FixedPoint => to_fixed_point(&triangle),
+    };
+    let area = match fixed {
+        Some([a, b, c]) => {
+            for (vertex, fixed) in triangle.iter_mut().zip([a, b, c]) {
+                let snapped = fixed.to_float();
+                (vertex.position.x, vertex.position.y) = (snapped.x, snapped.y);
+            }
+            FixedEdgeFunction::new(&a, &b).evaluate(&c) as f64
+        }
+        None => {
+            let [a, b, c] = triangle.map(|v| v.position.xy());
+            signed_triangle_area(&a, &b, &c)
+        }
+    };
+    if area == 0.0 {
+        return None;
+    }
+
+    let winding = if area > 0.0 { Winding::Clockwise } else { Winding::CounterClockwise };
+    let is_front_facing = winding == settings.front_face;
+    let culled = match settings.cull_mode {
+        CullMode::None => false,
+        CullMode::Back => !is_front_facing,
+        CullMode::Front => is_front_facing,
+    };
+    if culled {
+        return None;
+    }
+
+    // The rasterizer expects clockwise triangles, so flip the counter-clockwise ones
+    let [a, b, c] = triangle;
+    match winding {
+        Winding::Clockwise => Some([a, b, c]),
+        Winding::CounterClockwise => Some([a, c, b]),
+    }
+}
+
+/// The fixed-point positions of the vertices, if all of them are in range
+fn to_fixed_point<A>(triangle: &[ScreenVertex<A>; 3]) -> Option<[Fixed2; 3]> {
+    let [a, b, c] = triangle.each_ref().map(|v| Fixed2::from_float(&v.position.xy()));
+    Some([a?, b?, c?])
+}
+
+/// The edge functions of a triangle, traversed quad by quad along the rows of its bounding box. The edge function
+/// opposite to each vertex is the unnormalized barycentric weight of the vertex.
+trait QuadEdges {
+    /// Move to the quad whose top left pixel is at (x, y)
+    fn move_to(&mut self, x: usize, y: usize);
+
+    /// Move to the next quad of the row
+    fn next_quad(&mut self);
+
+    /// Whether the center of each pixel of the quad is inside the triangle, following the top-left fill rule, and the
+    /// barycentric weights of the vertices at it
+    fn get_quad(&self) -> [(bool, Float3); 4];
+
+    /// Whether the sample of a pixel of the quad is inside the triangle, and the barycentric weights at it. The offset
+    /// of the sample from the pixel center is given in 1/`SAMPLE_GRID` pixels.
+    fn get_sample(&self, pixel: usize, offset: [i8; 2]) -> (bool, Float3);
+}
+
+/// Floating-point edge functions
+struct FloatQuadEdges {
+    edges: [EdgeFunction; 3],
+    inverse_area: f64,
+    step_x: Float3,
+    step_y: Float3,
+    /// Offsets of the edge values of the pixels of a quad from its top left pixel
+    quad_offsets: [Float3; 4],
+    /// The center of the top left pixel of the current quad
+    center: Float2,
+    /// The edge values at the top left pixel of the current quad
+    values: Float3,
+    /// Bound of the rounding errors the steps add up to since the last `move_to`
+    tolerance: Float3,
+}
+
+impl FloatQuadEdges {
+    fn new(a: &Float2, b: &Float2, c: &Float2) -> Self {
+        let edges = [EdgeFunction::new(b, c), EdgeFunction::new(c, a), EdgeFunction::new(a, b)];
+        let step_x = Float3::new(edges[0].step_x, edges[1].step_x, edges[2].step_x);
+        let step_y = Float3::new(edges[0].step_y, edges[1].step_y, edges[2].step_y);
+        Self {
+            edges,
+            inverse_area: 1.0 / (2.0 * signed_triangle_area(a, b, c)),
+            step_x,
+            step_y,
+            quad_offsets: [Float3::zeros(), step_x, step_y, step_x + step_y],
+            center: Float2::zeros(),
+            values: Float3::zeros(),
+            tolerance: Float3::zeros(),
+        }
+    }
+
+    /// The edge values at the point from the stepped `values`. Values within the tolerance of zero are evaluated
+    /// directly instead, so a point exactly on an edge or a vertex gets the exact value, and the fill rule puts it
+    /// into exactly one of the triangles sharing the edge or vertex.
+    fn settle(&self, values: Float3, point: &Float2) -> Float3 {
+        let settle = |value: f64, tolerance: f64, edge: &EdgeFunction| {
+            if value.abs() <= tolerance { edge.evaluate(point) } else { value }
+        };
+        Float3::new(
+            settle(values.x, self.tolerance.x, &self.edges[0]),
+            settle(values.y, self.tolerance.y, &self.edges[1]),
+            settle(values.z, self.tolerance.z, &self.edges[2]),
+        )
+    }
+
+    fn is_inside(&self, values: &Float3) -> bool {
+        self.edges[0].is_inside(values.x) && self.edges[1].is_inside(values.y) && self.edges[2].is_inside(values.z)
+    }
+}
+
+impl QuadEdges for FloatQuadEdges {
+    fn move_to(&mut self, x: usize, y: usize) {
+        self.center = Float2::new(x as f64 + 0.5, y as f64 + 0.5);
+        let values = self.edges.map(|edge| edge.evaluate(&self.center));
+        self.values = Float3::new(values[0], values[1], values[2]);
+
+        // The steps stay within a tile, so there are at most TILE_SIZE / 2 of them and the values they pass through
+        // are bounded by the magnitude of the first value and the steps across a tile
+        let [step_x, step_y] = [self.step_x, self.step_y].map(|step| [step.x.abs(), step.y.abs(), step.z.abs()]);
+        let bound = |i: usize| (values[i].abs() + (step_x[i] + step_y[i]) * TILE_SIZE as f64) * TILE_SIZE as f64 * f64::EPSILON;
+        self.tolerance = Float3::new(bound(0), bound(1), bound(2));
+    }
+
+    fn next_quad(&mut self) {
+        self.center.x += 2.0;
+        self.values += self.step_x * 2.0;
+    }
+
+    fn get_quad(&self) -> [(bool, Float3); 4] {
+        std::array::from_fn(|pixel| {
+            let center = self.center + Float2::new((pixel % 2) as f64, (pixel / 2) as f64);
+            let values = self.settle(self.values + self.quad_offsets[pixel], &center);
+            (self.is_inside(&values), values * self.inverse_area)
+        })
+    }
+
+    fn get_sample(&self, pixel: usize, offset: [i8; 2]) -> (bool, Float3) {
+        let [dx, dy] = offset.map(|offset| offset as f64 / SAMPLE_GRID as f64);
+        let point = self.center + Float2::new((pixel % 2) as f64 + dx, (pixel / 2) as f64 + dy);
+        let values = self.settle(self.values + self.quad_offsets[pixel] + self.step_x * dx + self.step_y * dy, &point);
+        (self.is_inside(&values), values * self.inverse_area)
+    }
+}
+
+/// Fixed-point edge functions with exact integer values
+struct FixedQuadEdges {
+    edges: [FixedEdgeFunction; 3],
+    inverse_area: f64,
+    step_x: [i64; 3],
+    step_y: [i64; 3],
+    /// Offsets of the edge values of the pixels of a quad from its top left pixel
+    quad_offsets: [[i64; 3]; 4],
+    /// The edge values at the top left pixel of the current quad
+    values: [i64; 3],
+}
+
+impl FixedQuadEdges {
+    fn new(a: &Fixed2, b: &Fixed2, c: &Fixed2) -> Self {
+        let edges = [FixedEdgeFunction::new(b, c), FixedEdgeFunction::new(c, a), FixedEdgeFunction::new(a, b)];
+        let step_x = edges.map(|edge| edge.step_x);
+        let step_y = edges.map(|edge| edge.step_y);
+        Self {
+            edges,
+            inverse_area: 1.0 / edges[0].evaluate(a) as f64,
+            step_x,
+            step_y,
+            quad_offsets: [[0; 3], step_x, step_y, std::array::from_fn(|i| step_x[i] + step_y[i])],
+            values: [0; 3],
+        }
+    }
+}
+
+impl QuadEdges for FixedQuadEdges {
+    fn move_to(&mut self, x: usize, y: usize) {
+        let center = Fixed2::pixel_center(x, y);
+        self.values = self.edges.map(|edge| edge.evaluate(&center));
+    }
 
-        let bbox = determine_bounding_box(&a, &b, &c, render_target.get_width(), render_target.get_height());
-        paint_in_triangle(&a, &b, &c, bbox, object.triangle_colors[i / 3], render_target);
+    fn next_quad(&mut self) {
+        for (value, step) in self.values.iter_mut().zip(self.step_x) {
+            *value += 2 * step;
+        }
+    }
+
+    fn get_quad(&self) -> [(bool, Float3); 4] {
+        self.quad_offsets.map(|offset| {
+            let values: [i64; 3] = std::array::from_fn(|i| self.values[i] + offset[i]);
+            let inside = (0..3).all(|i| self.edges[i].is_inside(values[i]));
+            (inside, Float3::new(values[0] as f64, values[1] as f64, values[2] as f64) * self.inverse_area)
+        })
+    }
+
+    fn get_sample(&self, pixel: usize, offset: [i8; 2]) -> (bool, Float3) {
+        // The steps are multiples of the subpixel scale, so the sample offsets change the values by exact integers
+        let [dx, dy] = offset.map(|offset| offset as i64);
+        let values: [i64; 3] = std::array::from_fn(|i| {
+            self.values[i] + self.quad_offsets[pixel][i] + (self.step_x[i] * dx + self.step_y[i] * dy) / SAMPLE_GRID
+        });
+        let inside = (0..3).all(|i| self.edges[i].is_inside(values[i]));
+        (inside, Float3::new(values[0] as f64, values[1] as f64, values[2] as f64) * self.inverse_area)
     }
 }
 
-fn paint_in_triangle(a: &Float3, b: &Float3, c: &Float3, bbox: BBox, color: Float3, render_target: &mut RenderTarget) -> () {
-    // Discard z-coordinate for triangle math
-    let a2d = Float2::new(a.x, a.y);
-    let b2d = Float2::new(b.x, b.y);
-    let c2d = Float2::new(c.x, c.y);
+/// Paint the pixels of the triangle within the bounding box whose centers are inside the triangle. The render target
+/// covers the image from `origin` on, which is either the whole image or a tile of it.
+///
+/// The edge functions of the triangle are stepped incrementally from pixel to pixel. Pixel centers exactly on an edge
+/// are painted only for top and left edges, so triangles sharing an edge never both paint a pixel.
+///
+/// A multisampled render target is painted at the samples inside the triangle instead. Each pixel with a covered sample
+/// is shaded once at its center, and the color is stored in the covered samples that pass the depth test.
+fn paint_in_triangle<A: Interpolate>(
+    triangle: &[ScreenVertex<A>; 3],
+    bbox: BBox,
+    fragment_shader: &impl FragmentShader<A>,
+    render_target: &mut RenderTarget,
+    origin: [usize; 2],
+    precision: Precision,
+) {
+    // The vertices of fixed-point triangles are already snapped, so converting them again is exact
+    let fixed = match precision {
+        Precision::Float => None,
+        Precision::FixedPoint => to_fixed_point(triangle),
+    };
+    match fixed {
+        Some([a, b, c]) => {
+            paint_with_edges(triangle, FixedQuadEdges::new(&a, &b, &c), bbox, fragment_shader, render_target, origin);
+        }
+        None => {
+            // Discard z-coordinate for triangle math
+            let [a, b, c] = triangle.map(|v| v.position.xy());
+            paint_with_edges(triangle, FloatQuadEdges::new(&a, &b, &c), bbox, fragment_shader, render_target, origin);
+        }
+    }
+}
+
+fn paint_with_edges<A: Interpolate>(
+    triangle: &[ScreenVertex<A>; 3],
+    mut edges: impl QuadEdges,
+    bbox: BBox,
+    fragment_shader: &impl FragmentShader<A>,
+    render_target: &mut RenderTarget,
+    origin: [usize; 2],
+) {
+    let [a, b, c] = triangle;
+
+    // Unlike the attributes, 1/w is linear in screen space and can use the screen-space weights directly
+    let vertex_inv_w = Float3::new(a.inv_w, b.inv_w, c.inv_w);
+    // Perspective-correct the screen-space weights: weight each vertex by 1/w and renormalize
+    let correct = |weights: Float3| {
+        let inv_w = vertex_inv_w.dot(&weights);
+        Float3::new(weights.x * a.inv_w / inv_w, weights.y * b.inv_w / inv_w, weights.z * c.inv_w / inv_w)
+    };
+    let sample_offsets = render_target.get_sample_count().get_offsets();
+
+    // Loop over 2x2 pixel quads in the bounding box. The varyings are computed for every pixel of a quad -
+    // also the ones outside the triangle - so their derivatives can be estimated from the differences
+    // between neighboring pixels.
+    let start_x = bbox.min_x & !1;
+    for quad_y in ((bbox.min_y & !1)..=bbox.max_y).step_by(2) {
+        for quad_x in (start_x..=bbox.max_x).step_by(2) {
+            // Evaluate the edge functions directly at the start of each row and each tile, and step them in between.
+            // The rounding errors of the steps do not add up, and are the same whether the image is split into
+            // tiles or not.
+            if quad_x == start_x || quad_x.is_multiple_of(TILE_SIZE) {
+                edges.move_to(quad_x, quad_y);
+            } else {
+                edges.next_quad();
+            }
+            let pixels = [[quad_x, quad_y], [quad_x + 1, quad_y], [quad_x, quad_y + 1], [quad_x + 1, quad_y + 1]];
+
+            // Bit mask of the covered samples of each pixel. Only pixels in the bounding box belong to the triangle.
+            let quad = edges.get_quad();
+            let coverage: [u32; 4] = std::array::from_fn(|i| {
+                let [x, y] = pixels[i];
+                if !(bbox.min_x..=bbox.max_x).contains(&x) || !(bbox.min_y..=bbox.max_y).contains(&y) {
+                    return 0;
+                }
+                if render_target.samples.is_none() {
+                    return quad[i].0 as u32;
+                }
+                zip(0.., sample_offsets).filter(|&(_, &offset)| edges.get_sample(i, offset).0).fold(0, |mask, (s, _)| mask | 1 << s)
+            });
+            if coverage == [0; 4] {
+                continue;
+            }
 
-    // Loop over pixels in the bounding box
-    for y in bbox.min_y..=bbox.max_y {
-        for x in bbox.min_x..=bbox.max_x {
-            let p = Float2::new(x as f64, y as f64);
+            let samples = std::array::from_fn::<_, 4, _>(|i| {
+                let [x, y] = pixels[i];
+                let p = Float2::new(x as f64 + 0.5, y as f64 + 0.5);
+                let corrected = correct(quad[i].1);
 
-            // Is the current pixel inside the current triangle?
-            let (inside, weights) = point_in_triangle(&a2d, &b2d, &c2d, &p);
-            if inside {
-                // Cacluate the camera depth on the triangle
-                let vertex_depths = Float3::new(a.z, b.z, c.z);
-                let depth = vertex_depths.dot(&weights);
+                // Cacluate the (linear) camera depth on the triangle
+                let depth = f64::barycentric(&a.position.z, &b.position.z, &c.position.z, &corrected);
+                let varyings = A::barycentric(&a.attributes, &b.attributes, &c.attributes, &corrected);
+                (p, depth, varyings)
+            });
 
-                // Only assign the color to the pixel, if this is the closest triangle at this point
-                if depth > render_target.depth_buffer[[x, y]] {
+            // Derivatives of the varyings along x and y, shared by the whole quad
+            let ddx = samples[1].2 + samples[0].2 * -1.0;
+            let ddy = samples[2].2 + samples[0].2 * -1.0;
+
+            for (i, ([x, y], (position, depth, varyings))) in zip(pixels, samples).enumerate() {
+                if coverage[i] == 0 {
                     continue;
                 }
-                // Apply the triangle's color to the image buffer
-                render_target.image_buffer[[x, y]] = color;
-                render_target.depth_buffer[[x, y]] = depth;
+                let [x, y] = [x - origin[0], y - origin[1]];
+                let fragment = Fragment { position, depth, varyings, ddx, ddy };
+                let Some(sample_buffers) = &mut render_target.samples else {
+                    // Only assign the color to the pixel, if this is the closest triangle at this point
+                    if depth > render_target.depth_buffer[[x, y]] {
+                        continue;
+                    }
+                    render_target.image_buffer[[x, y]] = fragment_shader.shade(&fragment);
+                    render_target.depth_buffer[[x, y]] = depth;
+                    continue;
+                };
+
+                // Test the depth of each covered sample, but shade the pixel only once for all of them
+                let mut color = None;
+                for (s, &offset) in sample_offsets.iter().enumerate() {
+                    if coverage[i] & 1 << s == 0 {
+                        continue;
+                    }
+                    let (_, weights) = edges.get_sample(i, offset);
+                    let sample_depth = f64::barycentric(&a.position.z, &b.position.z, &c.position.z, &correct(weights));
+                    let index = sample_buffers.get_index(x, y, s);
+                    if sample_depth > sample_buffers.depths[index] {
+                        continue;
+                    }
+                    sample_buffers.colors[index] = *color.get_or_insert_with(|| fragment_shader.shade(&fragment));
+                    sample_buffers.depths[index] = sample_depth;
+                }
             }
         }
     }
 }
 
+#[derive(Clone, Copy)]
 struct BBox {
     min_x: usize,
     min_y: usize,
@@ -89,18 +682,326 @@ struct BBox {
     max_y: usize
 }
 
-fn determine_bounding_box(a: &Float3, b: &Float3, c: &Float3, width: usize, height: usize) -> BBox {
+impl BBox {
+    fn get_width(&self) -> usize {
+        self.max_x - self.min_x + 1
+    }
+
+    fn get_height(&self) -> usize {
+        self.max_y - self.min_y + 1
+    }
+
+    /// The smallest box containing both boxes
+    fn union(&self, other: &BBox) -> BBox {
+        BBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// The part of the box inside the other one, which the boxes must overlap
+    fn intersect(&self, other: &BBox) -> BBox {
+        BBox {
+            min_x: self.min_x.max(other.min_x),
+            min_y: self.min_y.max(other.min_y),
+            max_x: self.max_x.min(other.max_x),
+            max_y: self.max_y.min(other.max_y),
+        }
+    }
+}
+
+/// The pixels whose centers may be covered by the triangle, limited to the image. Returns `None` if there are none.
+/// The bounding box of the triangle is extended by the margin, so samples away from the pixel centers are included.
+fn determine_bounding_box(a: &Float3, b: &Float3, c: &Float3, margin: f64, width: usize, height: usize) -> Option<BBox> {
     // Determine bounding box
     let min_x = f64::min(a.x, f64::min(b.x, c.x));
     let min_y = f64::min(a.y, f64::min(b.y, c.y));
     let max_x = f64::max(a.x, f64::max(b.x, c.x));
     let max_y = f64::max(a.y, f64::max(b.y, c.y));
 
-    // Convert bounding box to integers of the image buffer
-    let bbox_start_x = usize::clamp(min_x as usize, 0, width - 1);
-    let bbox_start_y = usize::clamp(min_y as usize, 0, height - 1);
-    let bbox_end_x = usize::clamp(max_x as usize + 1, 0, width - 1);
-    let bbox_end_y = usize::clamp(max_y as usize + 1, 0, height - 1);
+    // Convert bounding box to the pixels of the image buffer whose centers are inside it
+    let first_pixel = |min: f64| (min - 0.5 - margin).ceil().max(0.0);
+    let last_pixel = |max: f64, size: usize| (max - 0.5 + margin).floor().min(size as f64 - 1.0);
+    let (bbox_start_x, bbox_end_x) = (first_pixel(min_x), last_pixel(max_x, width));
+    let (bbox_start_y, bbox_end_y) = (first_pixel(min_y), last_pixel(max_y, height));
+    if !(bbox_start_x <= bbox_end_x && bbox_start_y <= bbox_end_y) {
+        return None;
+    }
+
+    Some(BBox { min_x: bbox_start_x as usize, min_y: bbox_start_y as usize, max_x: bbox_end_x as usize, max_y: bbox_end_y as usize })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::multisampling::SampleCount;
+
+    /// Pseudo-random number in -0.5..0.5, the same for the same arguments
+    fn jitter(x: usize, y: usize, seed: usize) -> f64 {
+        let hash = (x as f64 * 12.9898 + y as f64 * 78.233 + seed as f64 * 37.719).sin() * 43758.5453;
+        hash - hash.floor() - 0.5
+    }
+
+    /// A grid of quads split into two triangles each, whose shared corners are moved pseudo-randomly by up to 0.2
+    /// cells. The grid reaches a cell beyond each side of the image.
+    fn jittered_grid(width: usize, height: usize, cell: f64, seed: usize) -> Vec<[Float2; 3]> {
+        let (columns, rows) = ((width as f64 / cell).ceil() as usize + 2, (height as f64 / cell).ceil() as usize + 2);
+        let corner = |x: usize, y: usize| Float2::new(
+            (x as f64 - 1.0 + 0.4 * jitter(x, y, seed)) * cell,
+            (y as f64 - 1.0 + 0.4 * jitter(x, y, seed + 1)) * cell,
+        );
+        let mut triangles = Vec::new();
+        for y in 0..rows {
+            for x in 0..columns {
+                let [a, b, c, d] = [corner(x, y), corner(x + 1, y), corner(x, y + 1), corner(x + 1, y + 1)];
+                // Alternate the diagonal, so the edges meet at different angles
+                match (x + y) % 2 {
+                    0 => triangles.extend([[a, b, d], [a, d, c]]),
+                    _ => triangles.extend([[a, b, c], [b, d, c]]),
+                }
+            }
+        }
+        triangles
+    }
+
+    /// Shades with the varyings and their derivatives, so the output depends on the whole quad
+    struct DerivativeShader;
+
+    impl FragmentShader<Float3> for DerivativeShader {
+        fn shade(&self, fragment: &Fragment<Float3>) -> Float3 {
+            fragment.varyings + fragment.ddx * 0.5 + fragment.ddy * 0.25
+        }
+    }
+
+    /// Counts how often each pixel is shaded
+    struct CountingShader {
+        width: usize,
+        counts: Vec<AtomicUsize>,
+    }
+
+    impl CountingShader {
+        fn new(width: usize, height: usize) -> Self {
+            Self { width, counts: (0..width * height).map(|_| AtomicUsize::new(0)).collect() }
+        }
+
+        fn get_count(&self, x: usize, y: usize) -> usize {
+            self.counts[x + y * self.width].load(Ordering::Relaxed)
+        }
+    }
+
+    impl<V> FragmentShader<V> for CountingShader {
+        fn shade(&self, fragment: &Fragment<V>) -> Float3 {
+            let (x, y) = (fragment.position.x as usize, fragment.position.y as usize);
+            self.counts[x + y * self.width].fetch_add(1, Ordering::Relaxed);
+            Float3::zeros()
+        }
+    }
+
+    /// Screen-space triangles at depth 1 without attributes
+    fn flat_triangles(triangles: &[[Float2; 3]]) -> Vec<[ScreenVertex<Float3>; 3]> {
+        triangles.iter().map(|corners| corners.map(|p| ScreenVertex::new(Float3::new(p.x, p.y, 1.0), 1.0, Float3::zeros()))).collect()
+    }
+
+    /// How often each pixel is shaded when drawing the triangles: '.' for never, '#' for once, else the count
+    fn coverage_mask(triangles: &[[Float2; 3]], width: usize, height: usize, settings: &RenderSettings) -> Vec<String> {
+        let shader = CountingShader::new(width, height);
+        draw(&flat_triangles(triangles), &shader, &mut RenderTarget::new(width, height), settings);
+        (0..height).map(|y| (0..width).map(|x| match shader.get_count(x, y) {
+            0 => '.',
+            1 => '#',
+            count => char::from_digit(count as u32, 10).unwrap_or('*'),
+        }).collect()).collect()
+    }
+
+    /// Orient and rasterize the screen-space triangles
+    fn draw<A: Interpolate + Sync>(
+        triangles: &[[ScreenVertex<A>; 3]],
+        fragment_shader: &(impl FragmentShader<A> + Sync),
+        render_target: &mut RenderTarget,
+        settings: &RenderSettings,
+    ) {
+        let oriented: Vec<_> = triangles.iter().filter_map(|&triangle| orient_triangle(triangle, settings)).collect();
+        rasterize(&oriented, fragment_shader, render_target, settings);
+    }
+
+    /// Whether the triangle covers the pixel at (2, 2) when rendered with the given settings
+    fn is_drawn(vertices: &[Float2], settings: &RenderSettings) -> bool {
+        let mut render_target = RenderTarget::new(8, 8);
+        render2d(vertices, &[Float3::splat(1.0)], &mut render_target, settings).unwrap();
+        render_target.image_buffer[[2, 2]] == Float3::splat(1.0)
+    }
+
+    #[test]
+    fn any_number_of_threads_paints_the_same_pixels() {
+        let (width, height) = (200, 150);
+        // Two overlapping layers, whose depths cross, so the depth test decides between them
+        let mut triangles = Vec::new();
+        for (layer, cell) in [(0, 9.0), (1, 13.5)] {
+            for (i, corners) in jittered_grid(width, height, cell, layer * 2).into_iter().enumerate() {
+                triangles.push(corners.map(|p| {
+                    let depth = 2.5 + 0.6 * (p.x * 0.031 + p.y * 0.017 + layer as f64 * 1.7).sin();
+                    let color = Float3::new(p.x / width as f64, p.y / height as f64, (i % 7) as f64 / 7.0);
+                    ScreenVertex::new(Float3::new(p.x, p.y, depth), 1.0 / depth, color)
+                }));
+            }
+        }
+
+        for precision in [Precision::Float, Precision::FixedPoint] {
+            for sample_count in [None, Some(SampleCount::Four)] {
+                let render = |threads: usize| {
+                    let mut render_target = match sample_count {
+                        None => RenderTarget::new(width, height),
+                        Some(sample_count) => RenderTarget::new_multisampled(width, height, sample_count),
+                    };
+                    let settings = RenderSettings { cull_mode: CullMode::None, threads, precision, ..RenderSettings::default() };
+                    draw(&triangles, &DerivativeShader, &mut render_target, &settings);
+                    render_target
+                };
+
+                let serial = render(1);
+                for threads in [2, 5] {
+                    let parallel = render(threads);
+                    for y in 0..height {
+                        for x in 0..width {
+                            let [a, b] = [&serial, &parallel].map(|target| target.image_buffer[[x, y]]);
+                            let [a_depth, b_depth] = [&serial, &parallel].map(|target| target.depth_buffer[[x, y]]);
+                            assert!(
+                                [a.x, a.y, a.z, a_depth].map(f64::to_bits) == [b.x, b.y, b.z, b_depth].map(f64::to_bits),
+                                "pixel ({}, {}) with {:?}, {:?} and {} threads", x, y, precision, sample_count, threads,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_sharing_edges_shade_every_pixel_exactly_once() {
+        let (width, height) = (150, 100);
+        // A fan around a pixel center, so many edges pass through the same point
+        let center = Float2::new(75.5, 50.5);
+        let fan: Vec<[Float2; 3]> = (0..12).map(|i| {
+            let corner = |i: usize| {
+                let angle = (i % 12) as f64 * std::f64::consts::TAU / 12.0;
+                center + Float2::new(angle.cos(), angle.sin()) * (40.0 + 5.0 * jitter(i % 12, 0, 9))
+            };
+            [center, corner(i), corner(i + 1)]
+        }).collect();
+
+        for precision in [Precision::Float, Precision::FixedPoint] {
+            for threads in [1, 4] {
+                let settings = RenderSettings { cull_mode: CullMode::None, threads, precision, ..RenderSettings::default() };
+                for cell in [7.0, 10.5, 32.0] {
+                    let shader = CountingShader::new(width, height);
+                    draw(&flat_triangles(&jittered_grid(width, height, cell, 3)), &shader, &mut RenderTarget::new(width, height), &settings);
+                    for y in 0..height {
+                        for x in 0..width {
+                            assert_eq!(shader.get_count(x, y), 1, "pixel ({}, {}) of cells of {} with {:?}", x, y, cell, precision);
+                        }
+                    }
+                }
+
+                let shader = CountingShader::new(width, height);
+                draw(&flat_triangles(&fan), &shader, &mut RenderTarget::new(width, height), &settings);
+                for y in 0..height {
+                    for x in 0..width {
+                        let inside = (Float2::new(x as f64 + 0.5, y as f64 + 0.5) - center).length() < 30.0;
+                        let count = shader.get_count(x, y);
+                        assert!(if inside { count == 1 } else { count <= 1 }, "pixel ({}, {}) of the fan with {:?}", x, y, precision);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_point_coverage_matches_the_golden_mask() {
+        // Every vertex is on a pixel center and the left edge runs through pixel centers, so the fill rule decides
+        let triangle = [Float2::new(1.5, 0.5), Float2::new(6.5, 3.5), Float2::new(1.5, 5.5)];
+        let golden = ["........", ".##.....", ".####...", ".#####..", ".###....", "........"];
+        for precision in [Precision::FixedPoint, Precision::Float] {
+            let settings = RenderSettings { precision, ..RenderSettings::default() };
+            assert_eq!(coverage_mask(&[triangle], 8, 6, &settings), golden, "{:?}", precision);
+        }
+    }
+
+    #[test]
+    fn fixed_point_edges_through_pixel_centers_shade_each_pixel_once() {
+        // Four triangles around a pixel center, whose shared edges are diagonals through pixel centers
+        let corners = [Float2::new(0.5, 0.5), Float2::new(10.5, 0.5), Float2::new(10.5, 10.5), Float2::new(0.5, 10.5)];
+        let center = Float2::new(5.5, 5.5);
+        let triangles: Vec<_> = (0..4).map(|i| [corners[i], corners[(i + 1) % 4], center]).collect();
+        let settings = RenderSettings { precision: Precision::FixedPoint, ..RenderSettings::default() };
+
+        // The top and left edges of the square own their pixels, the right and bottom edges do not
+        let mut expected = vec![".".repeat(12); 12];
+        expected[..10].fill(format!("{}..", "#".repeat(10)));
+        assert_eq!(coverage_mask(&triangles, 12, 12, &settings), expected);
+    }
+
+    #[test]
+    fn fixed_point_handles_vertices_at_and_beyond_its_range() {
+        // At the edge of the range, the edge functions take their largest values. Beyond it, the triangles fall back
+        // to floating point. The shared diagonal runs through pixel centers either way.
+        for extent in [FIXED_POINT_RANGE, 2.0 * FIXED_POINT_RANGE, 1e15] {
+            let [a, b, c, d] = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|[x, y]| Float2::new(x, y) * extent);
+            let settings = RenderSettings { precision: Precision::FixedPoint, ..RenderSettings::default() };
+            assert_eq!(coverage_mask(&[[a, b, c], [a, c, d]], 6, 4, &settings), vec!["######"; 4], "{}", extent);
+        }
+    }
 
-    BBox { min_x: bbox_start_x, min_y: bbox_start_y, max_x: bbox_end_x, max_y: bbox_end_y }
-}
\ No newline at end of file
+    #[test]
+    fn multisampled_draws_only_resolve_the_pixels_they_may_cover() {
+        let triangle = [Float2::new(2.0, 2.0), Float2::new(30.0, 2.0), Float2::new(2.0, 30.0)];
+        for threads in [1, 4] {
+            let mut render_target = RenderTarget::new_multisampled(150, 150, SampleCount::Four);
+            // A pixel far from the triangle, in another tile, that only a resolve of the whole target would overwrite
+            render_target.image_buffer[[140, 140]] = Float3::splat(0.5);
+            let settings = RenderSettings { threads, ..RenderSettings::default() };
+            render2d(&triangle, &[Float3::splat(1.0)], &mut render_target, &settings).unwrap();
+            assert_eq!(render_target.image_buffer[[140, 140]], Float3::splat(0.5));
+
+            // Resolving everything changes nothing but that pixel
+            let image = render_target.image_buffer.crop(0, 0, 150, 150);
+            render_target.resolve();
+            for y in 0..150 {
+                for x in 0..150 {
+                    if (x, y) != (140, 140) {
+                        assert_eq!(render_target.image_buffer[[x, y]], image[[x, y]], "pixel ({}, {})", x, y);
+                    }
+                }
+            }
+            assert_eq!(render_target.image_buffer[[140, 140]], Float3::zeros());
+            // The pixels on the long edge are partially covered
+            let edge = render_target.image_buffer[[15, 16]].x;
+            assert!(edge > 0.0 && edge < 1.0, "{}", edge);
+        }
+    }
+
+    #[test]
+    fn render2d_culls_by_the_winding_in_the_image() {
+        // Right along the top edge, then down to the left: clockwise in the image
+        let clockwise = [Float2::new(0.0, 0.0), Float2::new(8.0, 0.0), Float2::new(0.0, 8.0)];
+        let counter_clockwise = [clockwise[0], clockwise[2], clockwise[1]];
+
+        for precision in [Precision::Float, Precision::FixedPoint] {
+            let back = RenderSettings { precision, ..RenderSettings::default() };
+            assert!(is_drawn(&clockwise, &back));
+            assert!(!is_drawn(&counter_clockwise, &back));
+
+            let front = RenderSettings { cull_mode: CullMode::Front, ..back.clone() };
+            assert!(!is_drawn(&clockwise, &front));
+            assert!(is_drawn(&counter_clockwise, &front));
+
+            let counter_clockwise_front = RenderSettings { front_face: Winding::CounterClockwise, ..back.clone() };
+            assert!(!is_drawn(&clockwise, &counter_clockwise_front));
+            assert!(is_drawn(&counter_clockwise, &counter_clockwise_front));
+
+            let settings = RenderSettings { cull_mode: CullMode::None, ..back };
+            assert!(is_drawn(&clockwise, &settings) && is_drawn(&counter_clockwise, &settings));
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::fs::write;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::deflate::zlib_compress;
+use crate::rendering::image::{quantize, AlphaBuffer, ImageBuffer};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Number of bits per color channel in a PNG file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+/// Write the image to a PNG file
+pub fn write_png_file(image: &ImageBuffer, alpha: Option<&AlphaBuffer>, bit_depth: BitDepth, path: impl AsRef<Path>) -> Result<()> {
+    let png_buffer = image_to_png_buffer(image, alpha, bit_depth)?;
+    write(path.as_ref(), &png_buffer).map_err(|why| Error::from(why).in_file(path.as_ref()))
+}
+
+/// Encode the image as a PNG file. With an alpha plane, which must have the size of the image, the file stores RGBA
+/// instead of RGB.
+pub fn image_to_png_buffer(image: &ImageBuffer, alpha: Option<&AlphaBuffer>, bit_depth: BitDepth) -> Result<Vec<u8>> {
+    if image.get_size() == 0 {
+        return Err(Error::EmptyImage);
+    }
+    if let Some(alpha) = alpha && (alpha.get_width(), alpha.get_height()) != (image.get_width(), image.get_height()) {
+        return Err(Error::InvalidImage(format!(
+            "the alpha plane is {}x{}, but the image is {}x{}",
+            alpha.get_width(), alpha.get_height(), image.get_width(), image.get_height()
+        )));
+    }
+    // PNG limits the dimensions to 31 bits
+    let too_large = || Error::ImageTooLarge { width: image.get_width(), height: image.get_height() };
+    let width: u32 = image.get_width().try_into().ok().filter(|&w| w <= i32::MAX as u32).ok_or_else(too_large)?;
+    let height: u32 = image.get_height().try_into().ok().filter(|&h| h <= i32::MAX as u32).ok_or_else(too_large)?;
+
+    let channels = if alpha.is_some() { 4 } else { 3 };
+    let bytes_per_channel = match bit_depth {
+        BitDepth::Eight => 1,
+        BitDepth::Sixteen => 2,
+    };
+    let bytes_per_pixel = channels * bytes_per_channel;
+    let row_size = image.get_width() * bytes_per_pixel;
+
+    // Quantize the pixels and filter each row
+    let mut filtered = Vec::with_capacity((row_size + 1) * image.get_height());
+    let mut previous_row = vec![0u8; row_size];
+    let mut row = Vec::with_capacity(row_size);
+    for y in 0..image.get_height() {
+        row.clear();
+        for x in 0..image.get_width() {
+            let color = image[[x, y]];
+            let opacity = alpha.map_or(1.0, |alpha| alpha[[x, y]]);
+            for &value in [color.r(), color.g(), color.b(), opacity][..channels].iter() {
+                match bit_depth {
+                    BitDepth::Eight => row.push(quantize(value, u8::MAX as f64) as u8),
+                    BitDepth::Sixteen => row.extend((quantize(value, u16::MAX as f64) as u16).to_be_bytes()),
+                }
+            }
+        }
+        filter_row(&row, &previous_row, bytes_per_pixel, &mut filtered);
+        std::mem::swap(&mut row, &mut previous_row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // Bit depth
+    header.push(8 * bytes_per_channel as u8);
+    // Color type: 2 for RGB, 6 for RGBA
+    header.push(if alpha.is_some() { 6 } else { 2 });
+    // Deflate compression, adaptive filtering and no interlacing
+    header.extend([0, 0, 0]);
+
+    let mut buffer = Vec::from(SIGNATURE);
+    write_chunk(&mut buffer, b"IHDR", &header);
+    write_chunk(&mut buffer, b"IDAT", &zlib_compress(&filtered));
+    write_chunk(&mut buffer, b"IEND", &[]);
+    Ok(buffer)
+}
+
+/// Append the row with the filter type that predicts it best, estimated by the smallest sum of absolute residuals
+fn filter_row(row: &[u8], previous_row: &[u8], bytes_per_pixel: usize, output: &mut Vec<u8>) {
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+    for filter_type in 0..5u8 {
+        let residuals: Vec<u8> = (0..row.len()).map(|i| {
+            let left = if i >= bytes_per_pixel { row[i - bytes_per_pixel] } else { 0 };
+            let up = previous_row[i];
+            let up_left = if i >= bytes_per_pixel { previous_row[i - bytes_per_pixel] } else { 0 };
+            let prediction = match filter_type {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                _ => paeth(left, up, up_left),
+            };
+            row[i].wrapping_sub(prediction)
+        }).collect();
+
+        // Residuals are signed, so small negative values count as small
+        let cost = residuals.iter().map(|&r| (r as i8).unsigned_abs() as u64).sum();
+        if best.as_ref().is_none_or(|(best_cost, _, _)| cost < *best_cost) {
+            best = Some((cost, filter_type, residuals));
+        }
+    }
+
+    if let Some((_, filter_type, residuals)) = best {
+        output.push(filter_type);
+        output.extend(residuals);
+    }
+}
+
+/// The Paeth predictor: whichever of the neighbors is closest to left + up - up_left
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (distance_left, distance_up, distance_up_left) =
+        ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Append a chunk with its length and CRC
+fn write_chunk(buffer: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    buffer.extend((data.len() as u32).to_be_bytes());
+    let start = buffer.len();
+    buffer.extend(chunk_type);
+    buffer.extend(data);
+    let crc = crc32(&buffer[start..]);
+    buffer.extend(crc.to_be_bytes());
+}
+
+/// The CRC-32 checksum of PNG chunks (the same as used by zip and gzip)
+pub fn crc32(data: &[u8]) -> u32 {
+    // Lookup table of the CRC of every byte value, with the reversed polynomial 0xEDB88320
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0u32, |crc, &byte| TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::deflate::tests::zlib_decompress;
+    use crate::vector_math::vector::Float3;
+
+    /// The decoded header fields (width, height, bit depth, color type) and the unfiltered samples of a PNG file,
+    /// checking the signature and the CRC of every chunk
+    fn decode_png(png: &[u8]) -> (u32, u32, u8, u8, Vec<u8>) {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut position = 8;
+        let (mut header, mut compressed) = (Vec::new(), Vec::new());
+        loop {
+            let length = u32::from_be_bytes(png[position..position + 4].try_into().unwrap()) as usize;
+            let chunk = &png[position + 4..position + 8 + length];
+            let crc = u32::from_be_bytes(png[position + 8 + length..position + 12 + length].try_into().unwrap());
+            assert_eq!(crc32(chunk), crc, "CRC mismatch");
+            let (chunk_type, data) = chunk.split_at(4);
+            match chunk_type {
+                b"IHDR" => header = data.to_vec(),
+                b"IDAT" => compressed.extend(data),
+                b"IEND" => break,
+                _ => panic!("unexpected chunk"),
+            }
+            position += 12 + length;
+        }
+        assert_eq!(position + 12, png.len());
+
+        let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        let (bit_depth, color_type) = (header[8], header[9]);
+        let channels = if color_type == 6 { 4 } else { 3 };
+        let bytes_per_pixel = channels * bit_depth as usize / 8;
+        let row_size = width as usize * bytes_per_pixel;
+
+        let filtered = zlib_decompress(&compressed);
+        assert_eq!(filtered.len(), (row_size + 1) * height as usize);
+        let mut samples = vec![0u8; row_size * height as usize];
+        for (y, row) in filtered.chunks_exact(row_size + 1).enumerate() {
+            for i in 0..row_size {
+                let at = |index: usize| samples[index];
+                let current = y * row_size + i;
+                let left = if i >= bytes_per_pixel { at(current - bytes_per_pixel) } else { 0 };
+                let up = if y > 0 { at(current - row_size) } else { 0 };
+                let up_left = if y > 0 && i >= bytes_per_pixel { at(current - row_size - bytes_per_pixel) } else { 0 };
+                let prediction = match row[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => paeth(left, up, up_left),
+                    _ => panic!("invalid filter type"),
+                };
+                samples[current] = row[1 + i].wrapping_add(prediction);
+            }
+        }
+        (width, height, bit_depth, color_type, samples)
+    }
+
+    /// An image with gradients, noise and flat areas, so every filter type gets a chance
+    fn test_image(width: usize, height: usize) -> (ImageBuffer, AlphaBuffer) {
+        let mut image = ImageBuffer::new(width, height);
+        let mut alpha = AlphaBuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let noise = ((x * 7919 + y * 104729) % 256) as f64 / 255.0;
+                image[[x, y]] = Float3::new(x as f64 / width as f64, noise, if y % 3 == 0 { 0.75 } else { 1.2 });
+                alpha[[x, y]] = (x + y) as f64 / (width + height) as f64;
+            }
+        }
+        (image, alpha)
+    }
+
+    #[test]
+    fn crc32_matches_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+    }
+
+    #[test]
+    fn encoded_images_decode_to_the_quantized_colors() {
+        for (width, height) in [(1, 1), (2, 3), (5, 1), (1, 7), (13, 11), (24, 8), (97, 33)] {
+            let (image, alpha) = test_image(width, height);
+            for bit_depth in [BitDepth::Eight, BitDepth::Sixteen] {
+                for alpha in [None, Some(&alpha)] {
+                    let png = image_to_png_buffer(&image, alpha, bit_depth).unwrap();
+                    let (decoded_width, decoded_height, depth, color_type, samples) = decode_png(&png);
+                    assert_eq!((decoded_width, decoded_height), (width as u32, height as u32));
+                    assert_eq!(color_type, if alpha.is_some() { 6 } else { 2 });
+
+                    let max = match bit_depth {
+                        BitDepth::Eight => u8::MAX as f64,
+                        BitDepth::Sixteen => u16::MAX as f64,
+                    };
+                    let values: Vec<f64> = match depth {
+                        8 => samples.iter().map(|&sample| sample as f64).collect(),
+                        16 => samples.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as f64).collect(),
+                        _ => panic!("unexpected bit depth {}", depth),
+                    };
+                    let mut expected = Vec::new();
+                    for y in 0..height {
+                        for x in 0..width {
+                            let color = image[[x, y]];
+                            expected.extend([color.r(), color.g(), color.b()].map(|value| quantize(value, max)));
+                            if let Some(alpha) = alpha {
+                                expected.push(quantize(alpha[[x, y]], max));
+                            }
+                        }
+                    }
+                    assert_eq!(values, expected, "{}x{} at {} bits", width, height, depth);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_empty_images_and_mismatched_alpha() {
+        assert!(matches!(image_to_png_buffer(&ImageBuffer::new(0, 4), None, BitDepth::Eight), Err(Error::EmptyImage)));
+        let (image, _) = test_image(4, 4);
+        let alpha = AlphaBuffer::new(3, 4);
+        assert!(matches!(image_to_png_buffer(&image, Some(&alpha), BitDepth::Eight), Err(Error::InvalidImage(_))));
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs::read;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::image::{DecodedImage, ImageBuffer};
+use crate::vector_math::vector::Float3;
+
+/// Read a PPM or PGM file. See [`ppm_buffer_to_image`] for the supported variants.
+pub fn read_ppm_file(path: impl AsRef<Path>) -> Result<DecodedImage> {
+    let data = read(path.as_ref()).map_err(|why| Error::from(why).in_file(path.as_ref()))?;
+    ppm_buffer_to_image(&data).map_err(|why| why.in_file(path.as_ref()))
+}
+
+/// Decode a PPM file, binary (P6) or ASCII (P3), or a PGM file, binary (P5) or ASCII (P2), with a maximum value of up
+/// to 65535. Gray values are copied to all three color channels. The files have no alpha channel.
+pub fn ppm_buffer_to_image(data: &[u8]) -> Result<DecodedImage> {
+    let (channels, binary) = match data.get(..2) {
+        Some(b"P2") => (1, false),
+        Some(b"P3") => (3, false),
+        Some(b"P5") => (1, true),
+        Some(b"P6") => (3, true),
+        _ => return Err(Error::InvalidImage("the PPM signature is missing".to_string())),
+    };
+
+    let mut reader = Reader { data, position: 2 };
+    let width = reader.next_number("width")?;
+    let height = reader.next_number("height")?;
+    let max_value = reader.next_number("maximum value")?;
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyImage);
+    }
+    if !(1..=u16::MAX as usize).contains(&max_value) {
+        return Err(Error::InvalidImage(format!("the maximum value {} is outside of 1..=65535", max_value)));
+    }
+    let sample_count = width.checked_mul(height)
+                            .and_then(|pixels| pixels.checked_mul(channels))
+                            .ok_or(Error::ImageTooLarge { width, height })?;
+
+    let samples: Vec<usize> = if binary {
+        // A single whitespace character separates the header from the samples, which are big-endian if they need two
+        // bytes
+        let bytes_per_sample = if max_value > u8::MAX as usize { 2 } else { 1 };
+        let start = reader.position + 1;
+        data.get(start..)
+            .and_then(|samples| samples.get(..sample_count.checked_mul(bytes_per_sample)?))
+            .ok_or_else(|| Error::InvalidImage("the PPM pixel data is truncated".to_string()))?
+            .chunks_exact(bytes_per_sample)
+            .map(|bytes| bytes.iter().fold(0, |value, &byte| value << 8 | byte as usize))
+            .collect()
+    } else {
+        (0..sample_count).map(|_| reader.next_number("sample")).collect::<Result<_>>()?
+    };
+    if let Some(sample) = samples.iter().find(|&&sample| sample > max_value) {
+        return Err(Error::InvalidImage(format!("the sample {} exceeds the maximum value {}", sample, max_value)));
+    }
+
+    let mut image = ImageBuffer::new(width, height);
+    let scale = 1.0 / max_value as f64;
+    for (i, pixel) in samples.chunks_exact(channels).enumerate() {
+        image[[i % width, i / width]] = match *pixel {
+            [gray] => Float3::splat(gray as f64 * scale),
+            [red, green, blue] => Float3::new(red as f64, green as f64, blue as f64) * scale,
+            _ => unreachable!(),
+        };
+    }
+    Ok(DecodedImage { image, alpha: None })
+}
+
+/// Reads the whitespace-separated decimal numbers of the header and of ASCII files
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    /// Skip whitespace and comments, which run from a '#' to the end of the line
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'#' {
+                while self.data.get(self.position).is_some_and(|&byte| byte != b'\n' && byte != b'\r') {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read the next number, describing it by `name` in errors
+    fn next_number(&mut self, name: &str) -> Result<usize> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.data.get(self.position).is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.position])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| Error::InvalidImage(format!("expected the {} at byte {}", name, start)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The colors of the decoded image row by row
+    fn decode(data: &[u8]) -> Vec<Float3> {
+        let decoded = ppm_buffer_to_image(data).unwrap();
+        assert!(decoded.alpha.is_none());
+        let image = decoded.image;
+        (0..image.get_height()).flat_map(|y| (0..image.get_width()).map(move |x| (x, y))).map(|(x, y)| image[[x, y]]).collect()
+    }
+
+    #[test]
+    fn reads_ascii_files_with_comments() {
+        let pgm = b"P2\n# A comment\n3 # width\n1\n4\n0 2\n4\n";
+        assert_eq!(decode(pgm), [Float3::splat(0.0), Float3::splat(0.5), Float3::splat(1.0)]);
+
+        let ppm = b"P3 1 2 10\t10 0 5\r\n0 10 10";
+        assert_eq!(decode(ppm), [Float3::new(1.0, 0.0, 0.5), Float3::new(0.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn reads_binary_files() {
+        // The sample after the single whitespace character following the header looks like whitespace itself
+        let pgm = b"P5 2 1 255\n\x20\xFF";
+        assert_eq!(decode(pgm), [Float3::splat(32.0 / 255.0), Float3::splat(1.0)]);
+
+        let ppm = b"P6\n2 1\n255\n\xFF\x00\x33\x00\x66\xFF";
+        assert_eq!(decode(ppm), [Float3::new(1.0, 0.0, 0.2), Float3::new(0.0, 0.4, 1.0)]);
+    }
+
+    #[test]
+    fn reads_big_endian_16_bit_samples() {
+        let ppm = b"P6 1 1 65535\n\xFF\xFF\x80\x00\x00\x01";
+        assert_eq!(decode(ppm), [Float3::new(1.0, 32768.0 / 65535.0, 1.0 / 65535.0)]);
+
+        // A maximum value above 255 needs two bytes per sample even if the samples are small
+        let pgm = b"P5 2 1 1000\n\x03\xE8\x01\xF4";
+        assert_eq!(decode(pgm), [Float3::splat(1.0), Float3::splat(0.5)]);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let invalid: [&[u8]; 6] = [
+            b"P4 1 1\n\x00",
+            b"P3 1 1 255 255 255",
+            b"P3 1 1 255 255 255 256",
+            b"P6 2 1 255\n\x00\x00\x00",
+            b"P5 1 1 70000\n\x00\x00",
+            b"P2 0 1 255\n",
+        ];
+        for data in invalid {
+            assert!(ppm_buffer_to_image(data).is_err(), "{:?}", String::from_utf8_lossy(data));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::f64::consts::PI;
+
+/// Mitchell-Netravali parameters recommended by their authors as a tradeoff between blurring and ringing
+const MITCHELL_B: f64 = 1.0 / 3.0;
+const MITCHELL_C: f64 = 1.0 / 3.0;
+/// Number of lobes of the Lanczos filter
+const LANCZOS_LOBES: f64 = 3.0;
+
+/// Reconstruction filter that weights the source pixels by their distance when resampling an image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResamplingFilter {
+    /// Unweighted average of the source pixels, or nearest neighbor when enlarging
+    #[default]
+    Box,
+    /// Linear falloff, or bilinear interpolation when enlarging
+    Tent,
+    /// Cubic Mitchell-Netravali filter, which stays sharp with little ringing
+    Mitchell,
+    /// Windowed sinc with three lobes, which is the sharpest but rings at hard edges
+    Lanczos,
+}
+
+impl ResamplingFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Some(Self::Box),
+            "tent" => Some(Self::Tent),
+            "mitchell" => Some(Self::Mitchell),
+            "lanczos" => Some(Self::Lanczos),
+            _ => None,
+        }
+    }
+
+    /// Distance from the center in pixels beyond which the filter is zero
+    pub fn get_radius(&self) -> f64 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Mitchell => 2.0,
+            Self::Lanczos => LANCZOS_LOBES,
+        }
+    }
+
+    /// The unnormalized weight at the distance `x` from the center, in pixels
+    pub fn evaluate(&self, x: f64) -> f64 {
+        let t = x.abs();
+        match self {
+            // Half-open, so a source pixel exactly between two target pixels counts only once
+            Self::Box => if (-0.5..0.5).contains(&x) { 1.0 } else { 0.0 },
+            Self::Tent => (1.0 - t).max(0.0),
+            Self::Mitchell => {
+                let (b, c) = (MITCHELL_B, MITCHELL_C);
+                let value = if t < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * t.powi(3) + (-18.0 + 12.0 * b + 6.0 * c) * t.powi(2) + (6.0 - 2.0 * b)
+                } else if t < 2.0 {
+                    (-b - 6.0 * c) * t.powi(3) + (6.0 * b + 30.0 * c) * t.powi(2) + (-12.0 * b - 48.0 * c) * t + (8.0 * b + 24.0 * c)
+                } else {
+                    0.0
+                };
+                value / 6.0
+            }
+            Self::Lanczos => {
+                if t >= LANCZOS_LOBES {
+                    0.0
+                } else {
+                    sinc(t) * sinc(t / LANCZOS_LOBES)
+                }
+            }
+        }
+    }
+
+    /// The source pixels along one axis that contribute to each pixel of the target, with their normalized weights.
+    /// When shrinking, the filter is stretched by the scale factor, so every source pixel contributes to the target.
+    /// The source must not be empty.
+    pub fn get_contributions(&self, source_size: usize, target_size: usize) -> Vec<Vec<(usize, f64)>> {
+        let scale = source_size as f64 / target_size as f64;
+        let stretch = scale.max(1.0);
+        let radius = self.get_radius() * stretch;
+        (0..target_size).map(|target| {
+            // The pixel center in source coordinates
+            let center = (target as f64 + 0.5) * scale;
+            let first = (center - radius).floor().max(0.0) as usize;
+            let last = ((center + radius).ceil() as usize).min(source_size);
+            let mut contributions: Vec<(usize, f64)> = (first..last)
+                .map(|source| (source, self.evaluate((source as f64 + 0.5 - center) / stretch)))
+                .filter(|&(_, weight)| weight != 0.0)
+                .collect();
+
+            // Normalize the weights, so flat areas keep their value. Source pixels outside of the image are left out.
+            let sum: f64 = contributions.iter().map(|(_, weight)| weight).sum();
+            if sum.abs() < 1e-12 {
+                return vec![((center as usize).min(source_size - 1), 1.0)];
+            }
+            for (_, weight) in &mut contributions {
+                *weight /= sum;
+            }
+            contributions
+        }).collect()
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
@@ -0,0 +1,24 @@
+use crate::objects::Model;
+use crate::rendering::pipeline::{Fragment, FragmentShader, VertexOutput, VertexShader};
+use crate::vector_math::vector::Float3;
+
+/// Paints every triangle with its color from `Model::triangle_colors`
+pub struct FlatColorShader;
+
+impl VertexShader for FlatColorShader {
+    type Varyings = Float3;
+
+    fn shade(&self, model: &Model, index: usize) -> VertexOutput<Float3> {
+        VertexOutput {
+            position: model.transform.vertex_to_world(&model.vertices[index]),
+            // All three vertices share the color, so it stays constant across the triangle
+            varyings: model.triangle_colors[index / 3],
+        }
+    }
+}
+
+impl FragmentShader<Float3> for FlatColorShader {
+    fn shade(&self, fragment: &Fragment<Float3>) -> Float3 {
+        fragment.varyings
+    }
+}
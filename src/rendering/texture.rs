@@ -0,0 +1,316 @@
+use std::fmt;
+
+use crate::rendering::image::ImageBuffer;
+use crate::vector_math::vector::{Float2, Float3};
+
+/// How texels are combined when sampling between texel centers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// Use the closest texel
+    Nearest,
+    /// Blend the four closest texels
+    #[default]
+    Bilinear,
+}
+
+/// How texture coordinates outside of 0..1 are mapped onto the texture
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Tile the texture
+    #[default]
+    Repeat,
+    /// Extend the edge texels
+    Clamp,
+    /// Tile the texture, flipping every other tile
+    Mirror,
+}
+
+/// How the mipmap levels are chosen from the level of detail
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MipmapMode {
+    /// Always sample the full-resolution level
+    None,
+    /// Sample the closest level
+    Nearest,
+    /// Blend the two closest levels. Combined with bilinear filtering, this is trilinear filtering.
+    #[default]
+    Linear,
+}
+
+/// Settings for sampling a texture
+#[derive(Clone, Copy, Debug)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub mipmap_mode: MipmapMode,
+    /// Maximum number of samples taken along the direction in which the texture is stretched the most.
+    /// A value of 1 disables anisotropic filtering.
+    pub max_anisotropy: u32,
+    /// Wrap mode along the u-axis
+    pub wrap_u: WrapMode,
+    /// Wrap mode along the v-axis
+    pub wrap_v: WrapMode,
+}
+
+impl Sampler {
+    pub fn new(filter: Filter, wrap: WrapMode) -> Self {
+        Self { filter, mipmap_mode: MipmapMode::default(), max_anisotropy: 1, wrap_u: wrap, wrap_v: wrap }
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(Filter::default(), WrapMode::default())
+    }
+}
+
+/// An image mapped onto surfaces with texture coordinates, optionally with a chain of mipmaps.
+///
+/// The texture coordinates (0, 0) are the bottom left corner of the image and (1, 1) the top right corner,
+/// while row 0 of the image is the top row.
+pub struct Texture {
+    /// The full-resolution image followed by the mipmaps, each half the size of the previous level
+    levels: Vec<ImageBuffer>,
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Texture")
+         .field("width", &self.get_width())
+         .field("height", &self.get_height())
+         .field("levels", &self.levels.len())
+         .finish()
+    }
+}
+
+impl Texture {
+    /// Creates a texture without mipmaps
+    pub fn new(image: ImageBuffer) -> Self {
+        Self { levels: vec![image] }
+    }
+
+    /// Creates a texture with the full chain of mipmaps down to 1x1 pixel
+    pub fn with_mipmaps(image: ImageBuffer) -> Self {
+        let mut texture = Self::new(image);
+        texture.generate_mipmaps();
+        texture
+    }
+
+    /// Replace the mipmaps by a chain generated from the full-resolution image
+    pub fn generate_mipmaps(&mut self) {
+        self.levels.truncate(1);
+        loop {
+            let previous = &self.levels[self.levels.len() - 1];
+            if previous.get_size() == 0 || (previous.get_width() <= 1 && previous.get_height() <= 1) {
+                break;
+            }
+            let next = downsample(previous);
+            self.levels.push(next);
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.levels[0].get_width()
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.levels[0].get_height()
+    }
+
+    pub fn get_image(&self) -> &ImageBuffer {
+        &self.levels[0]
+    }
+
+    /// Number of levels including the full-resolution image
+    pub fn get_level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn get_level(&self, level: usize) -> &ImageBuffer {
+        &self.levels[level]
+    }
+
+    /// Sample the color of the full-resolution texture at the texture coordinates `uv`
+    pub fn sample(&self, uv: &Float2, sampler: &Sampler) -> Float3 {
+        self.sample_level(0, uv, sampler)
+    }
+
+    /// Sample the color of the texture at the texture coordinates `uv`, choosing the mipmap level from the
+    /// screen-space derivatives of the texture coordinates along x and y.
+    pub fn sample_grad(&self, uv: &Float2, duv_dx: &Float2, duv_dy: &Float2, sampler: &Sampler) -> Float3 {
+        if sampler.mipmap_mode == MipmapMode::None || self.levels.len() == 1 {
+            return self.sample(uv, sampler);
+        }
+
+        // The footprint of the pixel in texels
+        let size = Float2::new(self.get_width() as f64, self.get_height() as f64);
+        let (axis_x, axis_y) = (duv_dx * size, duv_dy * size);
+        let (length_x, length_y) = (axis_x.length(), axis_y.length());
+        let (major_axis, major_length, minor_length) = if length_x >= length_y {
+            (duv_dx, length_x, length_y)
+        } else {
+            (duv_dy, length_y, length_x)
+        };
+
+        // With anisotropic filtering, several samples are spread along the major axis of the footprint, so
+        // each sample only needs to cover the footprint divided by the sample count
+        let sample_count = if sampler.max_anisotropy > 1 && minor_length > 0.0 {
+            // Non-finite derivatives give a NaN ratio, which becomes 0 samples
+            ((major_length / minor_length).ceil().clamp(1.0, sampler.max_anisotropy as f64) as u32).max(1)
+        } else {
+            1
+        };
+        let level_of_detail = (major_length / sample_count as f64).max(1e-12).log2();
+
+        let mut color = Float3::zeros();
+        for i in 0..sample_count {
+            let offset = (i as f64 + 0.5) / sample_count as f64 - 0.5;
+            color += self.sample_lod(&(uv + major_axis * offset), level_of_detail, sampler);
+        }
+        color / sample_count as f64
+    }
+
+    /// Sample the texture at the (fractional) mipmap level given by the level of detail
+    fn sample_lod(&self, uv: &Float2, level_of_detail: f64, sampler: &Sampler) -> Float3 {
+        let max_level = (self.levels.len() - 1) as f64;
+        let level = level_of_detail.clamp(0.0, max_level);
+        match sampler.mipmap_mode {
+            MipmapMode::None => self.sample_level(0, uv, sampler),
+            MipmapMode::Nearest => self.sample_level(level.round() as usize, uv, sampler),
+            MipmapMode::Linear => {
+                let lower = level.floor();
+                let color = self.sample_level(lower as usize, uv, sampler);
+                if lower == level {
+                    return color;
+                }
+                color.lerp(&self.sample_level(lower as usize + 1, uv, sampler), level - lower)
+            }
+        }
+    }
+
+    /// Sample a single mipmap level
+    fn sample_level(&self, level: usize, uv: &Float2, sampler: &Sampler) -> Float3 {
+        let image = &self.levels[level];
+        if image.get_size() == 0 {
+            return Float3::zeros();
+        }
+
+        // Continuous texel coordinates, where texel centers are at half-integer positions
+        let x = uv.x * image.get_width() as f64;
+        let y = (1.0 - uv.y) * image.get_height() as f64;
+        // Infinite or NaN coordinates, e.g. from degenerate triangles, hit no texel
+        if !x.is_finite() || !y.is_finite() {
+            return Float3::zeros();
+        }
+
+        match sampler.filter {
+            Filter::Nearest => get_texel(image, x.floor() as i64, y.floor() as i64, sampler),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                // Coordinates beyond the range of i64 saturate, so the neighbors must not overflow
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let (x1, y1) = (x0.saturating_add(1), y0.saturating_add(1));
+
+                let top = get_texel(image, x0, y0, sampler).lerp(&get_texel(image, x1, y0, sampler), tx);
+                let bottom = get_texel(image, x0, y1, sampler).lerp(&get_texel(image, x1, y1, sampler), tx);
+                top.lerp(&bottom, ty)
+            }
+        }
+    }
+}
+
+/// The texel at integer coordinates, which are wrapped onto the image
+fn get_texel(image: &ImageBuffer, x: i64, y: i64, sampler: &Sampler) -> Float3 {
+    let x = wrap(x, image.get_width(), sampler.wrap_u);
+    let y = wrap(y, image.get_height(), sampler.wrap_v);
+    image[[x, y]]
+}
+
+/// Halve the size of the image (rounding down, but at least 1 pixel) by averaging blocks of pixels.
+/// For odd sizes, the blocks at the end also include the last row or column.
+fn downsample(image: &ImageBuffer) -> ImageBuffer {
+    let width = (image.get_width() / 2).max(1);
+    let height = (image.get_height() / 2).max(1);
+    let mut result = ImageBuffer::new(width, height);
+
+    // Source range covered by each destination pixel along an axis
+    let source_range = |i: usize, size: usize, source_size: usize| {
+        let start = i * source_size / size;
+        let end = if i == size - 1 { source_size } else { (i + 1) * source_size / size };
+        start..end
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Float3::zeros();
+            let mut count = 0;
+            for sy in source_range(y, height, image.get_height()) {
+                for sx in source_range(x, width, image.get_width()) {
+                    sum += image[[sx, sy]];
+                    count += 1;
+                }
+            }
+            result[[x, y]] = sum / count as f64;
+        }
+    }
+    result
+}
+
+/// Map the texel index `i` into `0..size`
+fn wrap(i: i64, size: usize, mode: WrapMode) -> usize {
+    let size = size as i64;
+    let wrapped = match mode {
+        WrapMode::Repeat => i.rem_euclid(size),
+        WrapMode::Clamp => i.clamp(0, size - 1),
+        WrapMode::Mirror => {
+            // Every other tile is flipped, so the pattern repeats after two tiles
+            let i = i.rem_euclid(2 * size);
+            if i < size { i } else { 2 * size - 1 - i }
+        }
+    };
+    wrapped as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extreme_texture_coordinates_sample_finite_colors() {
+        let mut image = ImageBuffer::new(3, 2);
+        for (i, value) in [0.0, 0.2, 0.4, 0.6, 0.8, 1.0].into_iter().enumerate() {
+            image[[i % 3, i / 3]] = Float3::splat(value);
+        }
+        let texture = Texture::with_mipmaps(image);
+        let coordinates = [
+            Float2::new(1e300, -1e300),
+            Float2::new(f64::MAX, f64::MIN),
+            Float2::new(-1e20, 3e19),
+            Float2::new(f64::INFINITY, 0.5),
+            Float2::new(0.5, f64::NAN),
+        ];
+        let derivatives = [Float2::new(0.1, 0.0), Float2::new(f64::NAN, 0.0), Float2::new(f64::INFINITY, 1.0)];
+
+        for filter in [Filter::Nearest, Filter::Bilinear] {
+            for wrap in [WrapMode::Repeat, WrapMode::Clamp, WrapMode::Mirror] {
+                for mipmap_mode in [MipmapMode::None, MipmapMode::Nearest, MipmapMode::Linear] {
+                    let sampler = Sampler { mipmap_mode, max_anisotropy: 8, ..Sampler::new(filter, wrap) };
+                    for uv in coordinates {
+                        let color = texture.sample(&uv, &sampler);
+                        assert!(color.x.is_finite(), "{:?} at {:?}", sampler, uv);
+                        for duv_dx in derivatives {
+                            let color = texture.sample_grad(&uv, &duv_dx, &Float2::new(0.0, 0.05), &sampler);
+                            assert!(color.x.is_finite(), "{:?} at {:?} with {:?}", sampler, uv, duv_dx);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Clamping keeps the edge texels of far away coordinates
+        let sampler = Sampler::new(Filter::Bilinear, WrapMode::Clamp);
+        assert_eq!(texture.sample(&Float2::new(1e300, -1e300), &sampler), Float3::splat(1.0));
+        assert_eq!(texture.sample(&Float2::new(f64::MIN, f64::MAX), &sampler), Float3::splat(0.0));
+    }
+}
@@ -0,0 +1,250 @@
+use std::borrow::Cow;
+use std::fs::read;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::rendering::image::{AlphaBuffer, DecodedImage, ImageBuffer};
+use crate::vector_math::vector::Float3;
+
+const HEADER_SIZE: usize = 18;
+
+/// Kinds of pixels a TGA file can store
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PixelKind {
+    /// Indices into the color map
+    ColorMapped,
+    TrueColor,
+    Grayscale,
+}
+
+/// Read a TGA file. See [`tga_buffer_to_image`] for the supported variants.
+pub fn read_tga_file(path: impl AsRef<Path>) -> Result<DecodedImage> {
+    let data = read(path.as_ref()).map_err(|why| Error::from(why).in_file(path.as_ref()))?;
+    tga_buffer_to_image(&data).map_err(|why| why.in_file(path.as_ref()))
+}
+
+/// Decode a TGA file with true-color pixels of 15, 16, 24 or 32 bits, grayscale pixels of 8 bits (16 with alpha) or
+/// 8- or 16-bit indices into a color map, either uncompressed or run-length encoded. Both row orders and both column
+/// orders are supported. The image has an alpha plane if the image descriptor declares alpha bits.
+pub fn tga_buffer_to_image(data: &[u8]) -> Result<DecodedImage> {
+    let invalid = |message: &str| Error::InvalidImage(message.to_string());
+    if data.len() < HEADER_SIZE {
+        return Err(invalid("the TGA header is truncated"));
+    }
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+    let id_length = data[0] as usize;
+    let has_color_map = data[1] == 1;
+    let image_type = data[2];
+    let color_map_start = read_u16(3);
+    let color_map_length = read_u16(5);
+    let color_map_bits = data[7];
+    let width = read_u16(12);
+    let height = read_u16(14);
+    let bits_per_pixel = data[16];
+    let descriptor = data[17];
+
+    let (kind, run_length_encoded) = match image_type {
+        0 => return Err(invalid("the TGA file contains no image data")),
+        1 | 9 => (PixelKind::ColorMapped, image_type == 9),
+        2 | 10 => (PixelKind::TrueColor, image_type == 10),
+        3 | 11 => (PixelKind::Grayscale, image_type == 11),
+        _ => return Err(Error::UnsupportedImage(format!("TGA image type {}", image_type))),
+    };
+    match (kind, bits_per_pixel) {
+        (PixelKind::ColorMapped, 8 | 16) | (PixelKind::TrueColor, 15 | 16 | 24 | 32) | (PixelKind::Grayscale, 8 | 16) => (),
+        _ => return Err(Error::UnsupportedImage(format!("TGA {:?} pixels with {} bits", kind, bits_per_pixel))),
+    }
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyImage);
+    }
+
+    // The image ID and the color map precede the pixels
+    let color_map_offset = HEADER_SIZE + id_length;
+    let color_map_size = if has_color_map { color_map_length * (color_map_bits as usize).div_ceil(8) } else { 0 };
+    let color_map: Vec<(Float3, f64)> = match kind {
+        PixelKind::ColorMapped => {
+            if !has_color_map {
+                return Err(invalid("the color-mapped TGA file has no color map"));
+            }
+            if !matches!(color_map_bits, 15 | 16 | 24 | 32) {
+                return Err(Error::UnsupportedImage(format!("TGA color map entries with {} bits", color_map_bits)));
+            }
+            data.get(color_map_offset..color_map_offset + color_map_size)
+                .ok_or_else(|| invalid("the TGA color map is truncated"))?
+                .chunks_exact((color_map_bits as usize).div_ceil(8))
+                .map(|entry| decode_color(entry, color_map_bits))
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let bytes_per_pixel = (bits_per_pixel as usize).div_ceil(8);
+    let pixel_count = width * height;
+    let pixel_data = data.get(color_map_offset + color_map_size..).unwrap_or_default();
+    let pixels = if run_length_encoded {
+        Cow::Owned(decode_rle(pixel_data, bytes_per_pixel, pixel_count)?)
+    } else {
+        Cow::Borrowed(pixel_data.get(..pixel_count * bytes_per_pixel).ok_or_else(|| invalid("the TGA pixel data is truncated"))?)
+    };
+
+    // Bits 0 to 3 of the descriptor count the alpha bits, bit 4 marks right-to-left and bit 5 top-to-bottom order
+    let has_alpha = descriptor & 0x0F != 0;
+    let right_to_left = descriptor & 0x10 != 0;
+    let top_to_bottom = descriptor & 0x20 != 0;
+
+    let mut image = ImageBuffer::new(width, height);
+    let mut alpha = has_alpha.then(|| AlphaBuffer::filled(width, height, 1.0));
+    for (i, pixel) in pixels.chunks_exact(bytes_per_pixel).enumerate() {
+        let (color, opacity) = match kind {
+            PixelKind::ColorMapped => {
+                let index = if bytes_per_pixel == 2 { u16::from_le_bytes([pixel[0], pixel[1]]) as usize } else { pixel[0] as usize };
+                *index.checked_sub(color_map_start)
+                      .and_then(|index| color_map.get(index))
+                      .ok_or_else(|| Error::InvalidImage(format!("the color map index {} is out of range", index)))?
+            }
+            PixelKind::TrueColor => decode_color(pixel, bits_per_pixel),
+            PixelKind::Grayscale => {
+                let opacity = if bytes_per_pixel == 2 { pixel[1] as f64 / 255.0 } else { 1.0 };
+                (Float3::splat(pixel[0] as f64 / 255.0), opacity)
+            }
+        };
+
+        let (column, row) = (i % width, i / width);
+        let x = if right_to_left { width - 1 - column } else { column };
+        let y = if top_to_bottom { row } else { height - 1 - row };
+        image[[x, y]] = color;
+        if let Some(alpha) = &mut alpha {
+            alpha[[x, y]] = opacity;
+        }
+    }
+    Ok(DecodedImage { image, alpha })
+}
+
+/// The color and opacity of a little-endian true-color value with 15, 16, 24 or 32 bits
+fn decode_color(bytes: &[u8], bits: u8) -> (Float3, f64) {
+    match bits {
+        15 | 16 => {
+            // 5 bits per channel in the order ARRRRRGGGGGBBBBB
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let channel = |shift: u16| ((value >> shift) & 0x1F) as f64 / 31.0;
+            let opacity = if bits == 16 && value & 0x8000 == 0 { 0.0 } else { 1.0 };
+            (Float3::new(channel(10), channel(5), channel(0)), opacity)
+        }
+        _ => {
+            let color = Float3::new(bytes[2] as f64, bytes[1] as f64, bytes[0] as f64) / 255.0;
+            let opacity = if bits == 32 { bytes[3] as f64 / 255.0 } else { 1.0 };
+            (color, opacity)
+        }
+    }
+}
+
+/// Decode `pixel_count` run-length encoded pixels. Each packet starts with a byte whose high bit marks a run of one
+/// repeated pixel and whose lower 7 bits are the number of pixels minus one. Packets may cross rows.
+fn decode_rle(data: &[u8], bytes_per_pixel: usize, pixel_count: usize) -> Result<Vec<u8>> {
+    let truncated = || Error::InvalidImage("the TGA run-length encoded data is truncated".to_string());
+    let size = pixel_count * bytes_per_pixel;
+    let mut pixels = Vec::new();
+    let mut i = 0;
+    while pixels.len() < size {
+        let header = *data.get(i).ok_or_else(truncated)?;
+        let count = (header & 0x7F) as usize + 1;
+        if header & 0x80 != 0 {
+            let pixel = data.get(i + 1..i + 1 + bytes_per_pixel).ok_or_else(truncated)?;
+            for _ in 0..count {
+                pixels.extend_from_slice(pixel);
+            }
+            i += 1 + bytes_per_pixel;
+        } else {
+            pixels.extend_from_slice(data.get(i + 1..i + 1 + count * bytes_per_pixel).ok_or_else(truncated)?);
+            i += 1 + count * bytes_per_pixel;
+        }
+    }
+    // The last packet may exceed the image
+    pixels.truncate(size);
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(red: u8, green: u8, blue: u8) -> Float3 {
+        Float3::new(red as f64, green as f64, blue as f64) / 255.0
+    }
+
+    /// A TGA header without image ID and color map
+    fn header(image_type: u8, width: u16, height: u16, bits: u8, descriptor: u8) -> Vec<u8> {
+        let mut data = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        data.extend(width.to_le_bytes());
+        data.extend(height.to_le_bytes());
+        data.extend([bits, descriptor]);
+        data
+    }
+
+    #[test]
+    fn reads_run_length_packets_crossing_rows() {
+        let mut data = header(10, 3, 2, 24, 0);
+        #[rustfmt::skip]
+        data.extend([
+            // A run of four blue pixels fills the bottom row and continues on the top row
+            0x83, 255, 0, 0,
+            // A raw packet of two pixels in the order BGR
+            0x01, 0, 0, 255, 0, 255, 0,
+        ]);
+        let decoded = tga_buffer_to_image(&data).unwrap();
+        let [blue, red, green] = [rgb(0, 0, 255), rgb(255, 0, 0), rgb(0, 255, 0)];
+        let expected = [[blue, red, green], [blue, blue, blue]];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                assert_eq!(decoded.image[[x, y]], *color, "pixel ({}, {})", x, y);
+            }
+        }
+        assert!(decoded.alpha.is_none());
+
+        // A run exceeding the image is cut off, while missing pixels are an error
+        data[HEADER_SIZE] = 0x85;
+        assert_eq!(tga_buffer_to_image(&data).unwrap().image[[2, 0]], blue);
+        data[HEADER_SIZE] = 0x82;
+        assert!(tga_buffer_to_image(&data).is_err());
+    }
+
+    #[test]
+    fn reads_right_to_left_and_top_to_bottom_rows() {
+        // Two rows of two 32-bit BGRA pixels with 8 alpha bits, stored from the top right
+        let mut data = header(2, 2, 2, 32, 0x08 | 0x10 | 0x20);
+        data.extend([1, 2, 3, 255, 4, 5, 6, 128, 7, 8, 9, 0, 10, 11, 12, 255]);
+        let decoded = tga_buffer_to_image(&data).unwrap();
+        let alpha = decoded.alpha.unwrap();
+        assert_eq!(decoded.image[[1, 0]], rgb(3, 2, 1));
+        assert_eq!(decoded.image[[0, 0]], rgb(6, 5, 4));
+        assert_eq!(decoded.image[[1, 1]], rgb(9, 8, 7));
+        assert_eq!(decoded.image[[0, 1]], rgb(12, 11, 10));
+        assert_eq!([alpha[[1, 0]], alpha[[0, 0]], alpha[[1, 1]], alpha[[0, 1]]], [1.0, 128.0 / 255.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn reads_color_maps_and_grayscale_with_alpha() {
+        // Color map of 16-bit entries starting at index 1, one opaque red and one transparent green entry
+        let mut data = header(1, 2, 1, 8, 0x01);
+        data[1] = 1;
+        data[3..8].copy_from_slice(&[1, 0, 2, 0, 16]);
+        data.extend([0x00, 0xFC, 0xE0, 0x03, 2, 1]);
+        let decoded = tga_buffer_to_image(&data).unwrap();
+        assert_eq!(decoded.image[[0, 0]], Float3::new(0.0, 1.0, 0.0));
+        assert_eq!(decoded.image[[1, 0]], Float3::new(1.0, 0.0, 0.0));
+        let alpha = decoded.alpha.unwrap();
+        assert_eq!((alpha[[0, 0]], alpha[[1, 0]]), (0.0, 1.0));
+
+        // The index 0 is before the start of the color map
+        *data.last_mut().unwrap() = 0;
+        assert!(tga_buffer_to_image(&data).is_err());
+
+        let mut data = header(3, 2, 1, 16, 0x08);
+        data.extend([51, 255, 255, 0]);
+        let decoded = tga_buffer_to_image(&data).unwrap();
+        assert_eq!(decoded.image[[0, 0]], Float3::splat(0.2));
+        assert_eq!(decoded.image[[1, 0]], Float3::splat(1.0));
+        let alpha = decoded.alpha.unwrap();
+        assert_eq!((alpha[[0, 0]], alpha[[1, 0]]), (1.0, 0.0));
+    }
+}
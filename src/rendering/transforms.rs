@@ -1,56 +1,262 @@
+use std::f64::consts::PI;
+
+use crate::rendering::clipping::Frustum;
+use crate::vector_math::matrix::{Mat3, Mat4};
+use crate::vector_math::quaternion::Quaternion;
 use crate::vector_math::vector::{Float2, Float3};
 
 pub struct Transform {
-    /// Rotation around the x-axis
-    pub pitch: f64,
-    /// Rotation around the z-axis (the up)
-    pub yaw: f64,
+    /// Rotation of the model, a unit quaternion
+    pub rotation: Quaternion,
+    /// Scale along each of the model axes
+    pub scale: Float3,
     /// World position
     pub position: Float3,
 }
 
 impl Transform {
-    pub fn new(rotation_around_x: f64, rotation_around_z: f64, position: Float3) -> Self {
-        Self { pitch: rotation_around_x, yaw: rotation_around_z, position }
+    pub fn new(rotation_around_x: f64, rotation_around_y: f64, position: Float3) -> Self {
+        let rotation = rotation_from_angles(rotation_around_x, rotation_around_y, 0.0);
+        Self { rotation, scale: Float3::new(1.0, 1.0, 1.0), position }
     }
 
     pub fn empty() -> Self {
-        Self { pitch: 0.0, yaw: 0.0, position: Float3::zeros() }
+        Self::new(0.0, 0.0, Float3::zeros())
+    }
+
+    /// The matrix transforming model space into world space: scaling, then rotation, then translation
+    pub fn get_model_matrix(&self) -> Mat4 {
+        let rotation = self.rotation.normalize().get_matrix();
+        Mat4::from_translation(&self.position) * Mat4::from_mat3(&(rotation * Mat3::from_diagonal(&self.scale)))
+    }
+
+    pub fn vertex_to_world(&self, vertex: &Float3) -> Float3 {
+        self.get_model_matrix().transform_point(vertex)
     }
+}
 
-    fn get_basis_vectors(&self) -> (Float3, Float3, Float3) {
-        // Trigonometry of rotation angles
-        let (sp, cp) = self.pitch.sin_cos();
-        let (sy, cy) = self.yaw.sin_cos();
+/// The axis of the image, which the field of view of a camera spans
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FovAxis {
+    #[default]
+    Vertical,
+    Horizontal,
+}
 
-        // Combined pitch and yaw - worked out by hand
-        let ihat = Float3::new(cy, 0.0, sy);
-        let jhat = Float3::new(sp*sy, cp, -sp*cy);
-        let khat = Float3::new(-cp*sy, sp, cp*cy);
+/// How a camera projects view space onto the image
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    /// Perspective projection, spanning the field of view of the camera
+    #[default]
+    Perspective,
+    /// Parallel projection, spanning `view_height` world units along the vertical image axis
+    Orthographic { view_height: f64 },
+}
 
-        (ihat, jhat, khat)
+/// A camera, which with no rotation looks down the positive z-axis with y as the up
+pub struct Camera {
+    /// World position
+    pub position: Float3,
+    /// Rotation around the x-axis
+    pub pitch: f64,
+    /// Rotation around the y-axis (the up)
+    pub yaw: f64,
+    /// Rotation around the viewing direction
+    pub roll: f64,
+    /// Perspective or orthographic projection
+    pub projection: Projection,
+    /// Field of view in degrees. Only used for perspective projection.
+    pub fov: f64,
+    /// The image axis spanned by the field of view
+    pub fov_axis: FovAxis,
+    /// Width / height of the image. `None` uses the aspect ratio of the render target.
+    pub aspect_ratio: Option<f64>,
+    /// Distance to the near clipping plane
+    pub near: f64,
+    /// Distance to the far clipping plane
+    pub far: f64,
+}
+
+impl Camera {
+    /// Creates a perspective camera at `position` looking down the positive z-axis. The fov must be in degrees.
+    pub fn new(position: Float3, fov: f64) -> Self {
+        Self {
+            position,
+            pitch: 0.0,
+            yaw: 0.0,
+            roll: 0.0,
+            projection: Projection::Perspective,
+            fov,
+            fov_axis: FovAxis::Vertical,
+            aspect_ratio: None,
+            near: 0.01,
+            far: 1000.0,
+        }
     }
 
-    pub fn vertex_to_world(&self, vertex: &Float3) -> Float3 {
-        let (ihat, jhat, khat) = self.get_basis_vectors();
-        self.transform_vector(&ihat, &jhat, &khat, vertex) + &self.position
+    /// Creates an orthographic camera at `position` looking down the positive z-axis, which shows
+    /// `view_height` world units along the vertical image axis
+    pub fn orthographic(position: Float3, view_height: f64) -> Self {
+        Self { projection: Projection::Orthographic { view_height }, ..Self::new(position, 60.0) }
+    }
+
+    /// Rotate the camera to look at `target`, keeping `up` pointing upwards in the image
+    pub fn look_at(&mut self, target: &Float3, up: &Float3) {
+        let forward = (*target - self.position).normalize();
+        self.pitch = forward.y.clamp(-1.0, 1.0).asin();
+        self.yaw = f64::atan2(-forward.x, forward.z);
+
+        // Roll the horizontal right vector of the camera onto the right vector defined by `up`
+        let (ihat, jhat, _) = basis_vectors(self.pitch, self.yaw, 0.0);
+        let right = up.cross(&forward);
+        self.roll = if right.length() > 1e-12 { f64::atan2(right.dot(&jhat), right.dot(&ihat)) } else { 0.0 };
+    }
+
+    /// Returns the right, up and forward vectors of the camera in world space
+    pub fn get_basis_vectors(&self) -> (Float3, Float3, Float3) {
+        basis_vectors(self.pitch, self.yaw, self.roll)
+    }
+
+    /// The matrix transforming world space into view space
+    pub fn get_view_matrix(&self) -> Mat4 {
+        let (right, up, forward) = self.get_basis_vectors();
+        // The rotation is orthonormal, so its inverse is the transpose
+        let rotation = Mat3::from_columns(&right, &up, &forward).transpose();
+        Mat4::from_mat3(&rotation) * Mat4::from_translation(&-self.position)
     }
 
-    fn transform_vector(&self, ih: &Float3, jh: &Float3, kh: &Float3, vertex: &Float3) -> Float3 {
-        ih * vertex.x + jh * vertex.y + kh * vertex.z
+    /// Transform a world-space position into view space, where the camera looks down the positive z-axis
+    pub fn world_to_view(&self, vertex: &Float3) -> Float3 {
+        let (right, up, forward) = self.get_basis_vectors();
+        let offset = *vertex - self.position;
+        Float3::new(offset.dot(&right), offset.dot(&up), offset.dot(&forward))
     }
+
+    /// The aspect ratio used when rendering to a target of the given size
+    pub fn get_aspect_ratio(&self, screen_size: &Float2) -> f64 {
+        self.aspect_ratio.unwrap_or(screen_size.x / screen_size.y)
+    }
+
+    /// Half the width and height of the view plane. For perspective projections, the view plane is at a
+    /// distance of 1 from the camera.
+    fn get_half_extents(&self, aspect_ratio: f64) -> (f64, f64) {
+        match self.projection {
+            Projection::Perspective => {
+                let tan_half_fov = f64::tan(self.fov / 360.0 * PI);
+                match self.fov_axis {
+                    FovAxis::Vertical => (tan_half_fov * aspect_ratio, tan_half_fov),
+                    FovAxis::Horizontal => (tan_half_fov, tan_half_fov / aspect_ratio),
+                }
+            }
+            Projection::Orthographic { view_height } => (view_height / 2.0 * aspect_ratio, view_height / 2.0),
+        }
+    }
+
+    /// The view-space frustum visible on a render target of the given size
+    pub fn get_frustum(&self, screen_size: &Float2) -> Frustum {
+        let (half_width, half_height) = self.get_half_extents(self.get_aspect_ratio(screen_size));
+        match self.projection {
+            Projection::Perspective => Frustum::perspective(half_width, half_height, self.near, self.far),
+            Projection::Orthographic { .. } => Frustum::orthographic(half_width, half_height, self.near, self.far),
+        }
+    }
+
+    /// The w-coordinate, which the projection divides by: the depth for perspective projections and 1 for
+    /// orthographic projections
+    pub fn get_w(&self, vertex_view: &Float3) -> f64 {
+        match self.projection {
+            Projection::Perspective => vertex_view.z,
+            Projection::Orthographic { .. } => 1.0,
+        }
+    }
+
+    /// Project a view-space vertex position into screen-space position [pixel coordinates], with the origin in the
+    /// top-left corner and the y-axis pointing down. The z-coordinate of the result is the view-space depth.
+    /// 
+    /// The vertex must be in front of the camera, so triangles should be clipped before projection.
+    pub fn view_to_screen(&self, vertex_view: &Float3, screen_size: &Float2) -> Float3 {
+        let (half_width, half_height) = self.get_half_extents(self.get_aspect_ratio(screen_size));
+
+        // Normalized device coordinates in -1..1
+        let w = self.get_w(vertex_view);
+        let ndc_x = vertex_view.x / (w * half_width);
+        let ndc_y = vertex_view.y / (w * half_height);
+
+        Float3::new((ndc_x + 1.0) * screen_size.x / 2.0, (1.0 - ndc_y) * screen_size.y / 2.0, vertex_view.z)
+    }
+}
+
+/// Basis vectors of the rotation given by the angles (in radians)
+fn basis_vectors(pitch: f64, yaw: f64, roll: f64) -> (Float3, Float3, Float3) {
+    // Trigonometry of rotation angles
+    let (sp, cp) = pitch.sin_cos();
+    let (sy, cy) = yaw.sin_cos();
+    let (sr, cr) = roll.sin_cos();
+
+    // Combined pitch and yaw - worked out by hand
+    let ihat = Float3::new(cy, 0.0, sy);
+    let jhat = Float3::new(sp*sy, cp, -sp*cy);
+    let khat = Float3::new(-cp*sy, sp, cp*cy);
+
+    // Roll rotates the first two basis vectors around the third
+    (ihat * cr + jhat * sr, jhat * cr - ihat * sr, khat)
 }
 
-/// Transform vertex position into screen-space position [pixel coordinates]
-/// 
-/// The fov must be in radians
-pub fn vertex_to_screen(vertex: &Float3, transform: &Transform, screen_size: &Float2, fov: f64) -> Float3 {
-    let vertex_world = transform.vertex_to_world(vertex);
+/// The rotation given by the angles (in radians), the same rotation as the basis vectors of a camera with these
+/// angles: roll around the z-axis, then pitch around the x-axis, then yaw around the y-axis
+pub fn rotation_from_angles(pitch: f64, yaw: f64, roll: f64) -> Quaternion {
+    Quaternion::from_axis_angle(&Float3::new(0.0, 1.0, 0.0), -yaw)
+        * Quaternion::from_axis_angle(&Float3::new(1.0, 0.0, 0.0), -pitch)
+        * Quaternion::from_axis_angle(&Float3::new(0.0, 0.0, 1.0), roll)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::vector_math::matrix::tests::assert_mat3_near;
+
+    #[test]
+    fn look_at_centers_the_target_and_keeps_up_upwards() {
+        let target = Float3::new(0.5, -1.0, 2.0);
+        let positions = [Float3::new(0.0, 0.0, -3.0), Float3::new(4.0, 2.0, 1.0), Float3::new(-1.0, 5.0, 2.5)];
+        let ups = [Float3::new(0.0, 1.0, 0.0), Float3::new(1.0, 1.0, 0.0)];
+        for position in positions {
+            for up in ups {
+                let mut camera = Camera::new(position, 60.0);
+                camera.look_at(&target, &up);
 
-    let world_screen_height = f64::tan(fov/2.0) * 2.0;
-    let pixel_factor = screen_size.y as f64 / world_screen_height / vertex_world.z;
+                let distance = (target - position).length();
+                assert!(camera.world_to_view(&target).approx_eq(&Float3::new(0.0, 0.0, distance), 1e-9));
+                // The up vector stays in the vertical plane of the image, pointing up
+                let up_view = camera.world_to_view(&(position + up)) - camera.world_to_view(&position);
+                assert!(up_view.x.abs() < 1e-9 && up_view.y > 0.0, "{:?}", up_view);
 
-    let mut pixel_offset = Float2::new(vertex_world.x, vertex_world.y) * pixel_factor;
-    pixel_offset += screen_size / 2.0;
-    Float3::new(pixel_offset.x, pixel_offset.y, vertex_world.z)
-}
\ No newline at end of file
+                // The view matrix agrees with the direct transformation
+                let point = Float3::new(3.0, -2.0, 7.0);
+                assert!(camera.get_view_matrix().transform_point(&point).approx_eq(&camera.world_to_view(&point), 1e-9));
+            }
+        }
+    }
+
+    #[test]
+    fn rotations_round_trip_through_quaternions() {
+        // Straight up and down, yaw and roll rotate around the same axis, so they cannot be told apart as angles
+        for pitch in [-FRAC_PI_2, -0.4, 0.0, 0.4, FRAC_PI_2] {
+            let rotation = rotation_from_angles(pitch, -1.1, 0.8);
+            let (ihat, jhat, khat) = basis_vectors(pitch, -1.1, 0.8);
+            assert_mat3_near(&rotation.get_matrix(), &Mat3::from_columns(&ihat, &jhat, &khat));
+
+            let mut transform = Transform::empty();
+            transform.rotation = rotation;
+            assert_mat3_near(&transform.get_model_matrix().get_mat3(), &rotation.get_matrix());
+        }
+
+        let rotation = Quaternion::from_axis_angle(&Float3::new(1.0, 3.0, -2.0), 1.9);
+        let transform = Transform { rotation, scale: Float3::new(2.0, 1.0, 0.5), position: Float3::new(1.0, 2.0, 3.0) };
+        let point = Float3::new(0.5, -1.0, 4.0);
+        let expected = rotation.rotate(&(point * transform.scale)) + transform.position;
+        assert!(transform.vertex_to_world(&point).approx_eq(&expected, 1e-12));
+    }
+}
@@ -0,0 +1,20 @@
+use std::ops::{Add, Mul};
+
+use crate::vector_math::vector::Float3;
+
+/// Values that can be linearly interpolated, such as per-vertex attributes.
+///
+/// Implemented for every type that can be added and scaled by an f64.
+pub trait Interpolate: Copy + Add<Output = Self> + Mul<f64, Output = Self> {
+    /// Linear interpolation from `a` (t = 0) to `b` (t = 1)
+    fn lerp(a: &Self, b: &Self, t: f64) -> Self {
+        *a * (1.0 - t) + *b * t
+    }
+
+    /// Weighted sum of the values at the three corners of a triangle, using barycentric `weights`
+    fn barycentric(a: &Self, b: &Self, c: &Self, weights: &Float3) -> Self {
+        *a * weights.x + *b * weights.y + *c * weights.z
+    }
+}
+
+impl<T: Copy + Add<Output = T> + Mul<f64, Output = T>> Interpolate for T {}
@@ -0,0 +1,302 @@
+use std::ops::Mul;
+
+use crate::vector_math::vector::{Float3, Float4};
+
+/// 3x3 matrix stored in row-major order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat3 {
+    pub rows: [[f64; 3]; 3],
+}
+
+impl Mat3 {
+    pub fn new(rows: [[f64; 3]; 3]) -> Self {
+        Self { rows }
+    }
+
+    pub fn identity() -> Self {
+        Self::from_diagonal(&Float3::new(1.0, 1.0, 1.0))
+    }
+
+    /// Matrix with `diagonal` on the diagonal and zeros elsewhere, i.e. a scaling matrix
+    pub fn from_diagonal(diagonal: &Float3) -> Self {
+        Self::new([
+            [diagonal.x, 0.0, 0.0],
+            [0.0, diagonal.y, 0.0],
+            [0.0, 0.0, diagonal.z],
+        ])
+    }
+
+    /// Matrix whose columns are the given vectors, e.g. the basis vectors of a rotation
+    pub fn from_columns(c0: &Float3, c1: &Float3, c2: &Float3) -> Self {
+        Self::new([
+            [c0.x, c1.x, c2.x],
+            [c0.y, c1.y, c2.y],
+            [c0.z, c1.z, c2.z],
+        ])
+    }
+
+    pub fn get_column(&self, index: usize) -> Float3 {
+        Float3::new(self.rows[0][index], self.rows[1][index], self.rows[2][index])
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_columns(&self.get_row(0), &self.get_row(1), &self.get_row(2))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        // The triple product of the rows
+        self.get_row(0).dot(&self.get_row(1).cross(&self.get_row(2)))
+    }
+
+    /// The inverse matrix, or `None` if the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-300 {
+            return None;
+        }
+        // The columns of the inverse are the cross products of the rows, divided by the determinant
+        let (r0, r1, r2) = (self.get_row(0), self.get_row(1), self.get_row(2));
+        let inverse_determinant = 1.0 / determinant;
+        Some(Self::from_columns(
+            &(r1.cross(&r2) * inverse_determinant),
+            &(r2.cross(&r0) * inverse_determinant),
+            &(r0.cross(&r1) * inverse_determinant),
+        ))
+    }
+
+    fn get_row(&self, index: usize) -> Float3 {
+        let [x, y, z] = self.rows[index];
+        Float3::new(x, y, z)
+    }
+}
+
+impl Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+    fn mul(self, rhs: Mat3) -> Self::Output {
+        let mut rows = [[0.0; 3]; 3];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Mat3::new(rows)
+    }
+}
+
+impl Mul<Float3> for Mat3 {
+    type Output = Float3;
+    fn mul(self, rhs: Float3) -> Self::Output {
+        Float3::new(self.get_row(0).dot(&rhs), self.get_row(1).dot(&rhs), self.get_row(2).dot(&rhs))
+    }
+}
+
+impl Mul<f64> for Mat3 {
+    type Output = Mat3;
+    fn mul(self, rhs: f64) -> Self::Output {
+        Mat3::new(self.rows.map(|row| row.map(|value| value * rhs)))
+    }
+}
+
+
+/// 4x4 matrix stored in row-major order. Vectors are treated as columns, so `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub rows: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    pub fn identity() -> Self {
+        Self::from_mat3(&Mat3::identity())
+    }
+
+    /// Affine matrix applying the linear transformation `m`
+    pub fn from_mat3(m: &Mat3) -> Self {
+        let [r0, r1, r2] = m.rows;
+        Self::new([
+            [r0[0], r0[1], r0[2], 0.0],
+            [r1[0], r1[1], r1[2], 0.0],
+            [r2[0], r2[1], r2[2], 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn from_translation(translation: &Float3) -> Self {
+        let mut m = Self::identity();
+        m.rows[0][3] = translation.x;
+        m.rows[1][3] = translation.y;
+        m.rows[2][3] = translation.z;
+        m
+    }
+
+    pub fn from_scale(scale: &Float3) -> Self {
+        Self::from_mat3(&Mat3::from_diagonal(scale))
+    }
+
+    /// The upper-left 3x3 part of the matrix, i.e. the linear transformation without translation
+    pub fn get_mat3(&self) -> Mat3 {
+        let [r0, r1, r2, _] = self.rows;
+        Mat3::new([
+            [r0[0], r0[1], r0[2]],
+            [r1[0], r1[1], r1[2]],
+            [r2[0], r2[1], r2[2]],
+        ])
+    }
+
+    /// The matrix transforming normals, which is the inverse transpose of the linear part.
+    /// Returns `None` if the matrix is singular.
+    pub fn get_normal_matrix(&self) -> Option<Mat3> {
+        self.get_mat3().inverse().map(|m| m.transpose())
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Self::new(rows)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        // Laplace expansion along the first row
+        (0..4).map(|j| self.rows[0][j] * self.cofactor(0, j)).sum()
+    }
+
+    /// The inverse matrix, or `None` if the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-300 {
+            return None;
+        }
+        // The inverse is the transposed cofactor matrix (the adjugate) divided by the determinant
+        let inverse_determinant = 1.0 / determinant;
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.cofactor(j, i) * inverse_determinant;
+            }
+        }
+        Some(Self::new(rows))
+    }
+
+    /// Transform a position, applying the translation
+    pub fn transform_point(&self, point: &Float3) -> Float3 {
+        let p = *self * Float4::from_point(point);
+        // Projective matrices may change w, so divide it out again
+        if p.w != 1.0 && p.w != 0.0 { p.xyz() / p.w } else { p.xyz() }
+    }
+
+    /// Transform a direction, ignoring the translation
+    pub fn transform_direction(&self, direction: &Float3) -> Float3 {
+        (*self * Float4::from_direction(direction)).xyz()
+    }
+
+    /// Signed determinant of the 3x3 matrix left when removing `row` and `column`
+    fn cofactor(&self, row: usize, column: usize) -> f64 {
+        let mut minor = [[0.0; 3]; 3];
+        let rows = (0..4).filter(|&i| i != row);
+        for (minor_row, i) in minor.iter_mut().zip(rows) {
+            let columns = (0..4).filter(|&j| j != column);
+            for (value, j) in minor_row.iter_mut().zip(columns) {
+                *value = self.rows[i][j];
+            }
+        }
+        let sign = if (row + column).is_multiple_of(2) { 1.0 } else { -1.0 };
+        sign * Mat3::new(minor).determinant()
+    }
+
+    fn get_row(&self, index: usize) -> Float4 {
+        let [x, y, z, w] = self.rows[index];
+        Float4::new(x, y, z, w)
+    }
+}
+
+impl Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Mat4::new(rows)
+    }
+}
+
+impl Mul<Float4> for Mat4 {
+    type Output = Float4;
+    fn mul(self, rhs: Float4) -> Self::Output {
+        Float4::new(
+            self.get_row(0).dot(&rhs),
+            self.get_row(1).dot(&rhs),
+            self.get_row(2).dot(&rhs),
+            self.get_row(3).dot(&rhs),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::vector_math::quaternion::Quaternion;
+
+    pub(crate) fn assert_mat3_near(a: &Mat3, b: &Mat3) {
+        let near = (0..3).all(|i| Float3::new(a.rows[i][0], a.rows[i][1], a.rows[i][2])
+                                       .approx_eq(&Float3::new(b.rows[i][0], b.rows[i][1], b.rows[i][2]), 1e-9));
+        assert!(near, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_mat4_near(a: &Mat4, b: &Mat4) {
+        assert!((0..4).all(|i| a.get_row(i).approx_eq(&b.get_row(i), 1e-9)), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn mat4_inverse_undoes_the_matrix() {
+        let rotation = Quaternion::from_axis_angle(&Float3::new(1.0, 2.0, -0.5), 0.7).get_matrix();
+        let affine = Mat4::from_translation(&Float3::new(3.0, -1.0, 2.5))
+            * Mat4::from_mat3(&rotation)
+            * Mat4::from_scale(&Float3::new(2.0, 0.5, -3.0));
+        let projective = Mat4::new([
+            [1.5, 0.0, 0.2, 0.0],
+            [0.0, 2.0, -0.3, 0.0],
+            [0.0, 0.0, 1.0, -0.1],
+            [0.0, 0.0, 1.0, 0.0],
+        ]);
+
+        for m in [affine, projective, affine * projective] {
+            let inverse = m.inverse().unwrap();
+            assert_mat4_near(&(m * inverse), &Mat4::identity());
+            assert_mat4_near(&(inverse * m), &Mat4::identity());
+            let point = Float3::new(0.3, -2.0, 4.0);
+            assert!(inverse.transform_point(&m.transform_point(&point)).approx_eq(&point, 1e-9));
+        }
+
+        // The inverse of a rotation is its transpose
+        assert_mat4_near(&Mat4::from_mat3(&rotation).inverse().unwrap(), &Mat4::from_mat3(&rotation.transpose()));
+        assert_mat3_near(&(rotation.inverse().unwrap() * rotation), &Mat3::identity());
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Mat4::from_scale(&Float3::new(1.0, 0.0, 1.0)).inverse().is_none());
+        let dependent_rows = Mat4::new([[1.0, 2.0, 3.0, 4.0], [2.0, 4.0, 6.0, 8.0], [0.0, 1.0, 0.0, 1.0], [1.0, 0.0, 0.0, 0.0]]);
+        assert!(dependent_rows.inverse().is_none());
+        assert!(Mat3::from_diagonal(&Float3::new(2.0, 3.0, 0.0)).inverse().is_none());
+        assert!(Mat4::from_scale(&Float3::new(1.0, 0.0, 1.0)).get_normal_matrix().is_none());
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular_to_surfaces() {
+        let m = Mat4::from_mat3(&Quaternion::from_axis_angle(&Float3::new(0.0, 1.0, 1.0), 1.2).get_matrix())
+            * Mat4::from_scale(&Float3::new(4.0, 1.0, 0.25));
+        let (tangent, normal) = (Float3::new(1.0, -1.0, 0.0), Float3::new(1.0, 1.0, 2.0));
+        let transformed_normal = m.get_normal_matrix().unwrap() * normal;
+        assert!(m.transform_direction(&tangent).dot(&transformed_normal).abs() < 1e-9);
+    }
+}
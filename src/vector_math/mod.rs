@@ -1,2 +1,5 @@
+pub mod interpolation;
+pub mod matrix;
+pub mod quaternion;
 pub mod vector;
 pub mod triangle;
\ No newline at end of file
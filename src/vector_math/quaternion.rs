@@ -0,0 +1,179 @@
+use std::ops::Mul;
+
+use crate::vector_math::matrix::Mat3;
+use crate::vector_math::vector::Float3;
+
+/// Quaternion `w + xi + yj + zk`. Unit quaternions represent rotations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// The rotation doing nothing
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Rotation by `angle` radians around `axis`
+    pub fn from_axis_angle(axis: &Float3, angle: f64) -> Self {
+        let (s, c) = (angle / 2.0).sin_cos();
+        let axis = axis.normalize();
+        Self::new(c, axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    /// The rotation axis and angle in radians. The axis is arbitrary for the identity rotation.
+    pub fn get_axis_angle(&self) -> (Float3, f64) {
+        let q = self.normalize();
+        let sin_half_angle = q.vector_part().length();
+        if sin_half_angle < 1e-12 {
+            return (Float3::new(1.0, 0.0, 0.0), 0.0);
+        }
+        (q.vector_part() / sin_half_angle, 2.0 * f64::atan2(sin_half_angle, q.w))
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let inverse_length = 1.0 / self.length();
+        Self::new(self.w * inverse_length, self.x * inverse_length, self.y * inverse_length, self.z * inverse_length)
+    }
+
+    /// The conjugate, which is the inverse rotation for unit quaternions
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Rotate a vector by the (unit) quaternion
+    pub fn rotate(&self, v: &Float3) -> Float3 {
+        // Optimized form of q * v * q^-1
+        let u = self.vector_part();
+        let t = u.cross(v) * 2.0;
+        *v + t * self.w + u.cross(&t)
+    }
+
+    /// The rotation matrix of the (unit) quaternion
+    pub fn get_matrix(&self) -> Mat3 {
+        Mat3::from_columns(
+            &self.rotate(&Float3::new(1.0, 0.0, 0.0)),
+            &self.rotate(&Float3::new(0.0, 1.0, 0.0)),
+            &self.rotate(&Float3::new(0.0, 0.0, 1.0)),
+        )
+    }
+
+    /// Spherical linear interpolation between the rotations `a` (t = 0) and `b` (t = 1) along the shortest arc
+    pub fn slerp(a: &Self, b: &Self, t: f64) -> Self {
+        let mut cos_angle = a.dot(b);
+        // q and -q are the same rotation, so flip b to take the shorter way around
+        let b = if cos_angle < 0.0 {
+            cos_angle = -cos_angle;
+            Self::new(-b.w, -b.x, -b.y, -b.z)
+        } else {
+            *b
+        };
+
+        // Fall back to linear interpolation for nearly identical rotations to avoid dividing by ~0
+        let (weight_a, weight_b) = if cos_angle > 1.0 - 1e-9 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos_angle.acos();
+            let inverse_sin = 1.0 / angle.sin();
+            (((1.0 - t) * angle).sin() * inverse_sin, (t * angle).sin() * inverse_sin)
+        };
+        Self::new(
+            a.w * weight_a + b.w * weight_b,
+            a.x * weight_a + b.x * weight_b,
+            a.y * weight_a + b.y * weight_b,
+            a.z * weight_a + b.z * weight_b,
+        ).normalize()
+    }
+
+    fn vector_part(&self) -> Float3 {
+        Float3::new(self.x, self.y, self.z)
+    }
+}
+
+/// Hamilton product. The rotation `a * b` applies `b` first.
+impl Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::vector_math::matrix::tests::assert_mat3_near;
+
+    fn rotations() -> [Quaternion; 4] {
+        [
+            Quaternion::identity(),
+            Quaternion::from_axis_angle(&Float3::new(0.0, 0.0, 1.0), FRAC_PI_2),
+            Quaternion::from_axis_angle(&Float3::new(1.0, -2.0, 0.5), 2.5),
+            Quaternion::from_axis_angle(&Float3::new(-0.3, 0.1, 1.0), -0.4),
+        ]
+    }
+
+    #[test]
+    fn rotation_matrix_matches_the_rotation() {
+        let v = Float3::new(0.5, -1.5, 2.0);
+        for q in rotations() {
+            let m = q.get_matrix();
+            assert!((m * v).approx_eq(&q.rotate(&v), 1e-12));
+            // Rotation matrices are orthonormal and keep the handedness
+            assert_mat3_near(&(m * m.transpose()), &Mat3::identity());
+            assert!((m.determinant() - 1.0).abs() < 1e-12);
+            // The conjugate rotates back
+            assert_mat3_near(&q.conjugate().get_matrix(), &m.transpose());
+        }
+
+        // A quarter turn around z turns x into y in a right-handed coordinate system
+        let quarter_turn = rotations()[1].get_matrix();
+        assert!((quarter_turn * Float3::new(1.0, 0.0, 0.0)).approx_eq(&Float3::new(0.0, 1.0, 0.0), 1e-12));
+    }
+
+    #[test]
+    fn products_rotate_by_the_right_factor_first() {
+        for a in rotations() {
+            for b in rotations() {
+                assert_mat3_near(&(a * b).get_matrix(), &(a.get_matrix() * b.get_matrix()));
+            }
+        }
+    }
+
+    #[test]
+    fn axis_angle_and_slerp() {
+        let axis = Float3::new(2.0, 1.0, -2.0);
+        let q = Quaternion::from_axis_angle(&axis, 1.3);
+        let (decoded_axis, angle) = q.get_axis_angle();
+        assert!(decoded_axis.approx_eq(&axis.normalize(), 1e-12));
+        assert!((angle - 1.3).abs() < 1e-12);
+
+        let halfway = Quaternion::slerp(&Quaternion::identity(), &q, 0.5);
+        assert_mat3_near(&halfway.get_matrix(), &Quaternion::from_axis_angle(&axis, 0.65).get_matrix());
+        // -q is the same rotation, and slerp must still take the short way
+        let negated = Quaternion::new(-q.w, -q.x, -q.y, -q.z);
+        assert_mat3_near(&Quaternion::slerp(&Quaternion::identity(), &negated, 0.5).get_matrix(), &halfway.get_matrix());
+    }
+}
@@ -1,29 +1,159 @@
 use crate::vector_math::vector::{Float2, Float3};
 
-/// Determine whether `point` is inside the triangle spanned by `v1`->`v2`->`v3`->`v1`.
+/// Determine whether `point` is inside the triangle spanned by `v1`->`v2`->`v3`->`v1`, following the top-left fill rule
+/// for points on its edges.
 pub fn point_in_triangle(v1: &Float2, v2: &Float2, v3: &Float2, point: &Float2) -> (bool, Float3) {
-    let area_12p = signed_triangle_area(v1, v2, point);
-    let area_23p = signed_triangle_area(v2, v3, point);
-    let area_31p = signed_triangle_area(v3, v1, point);
-    // If the points are all right of, we are inside. Assumes clockwise winding of vertices
-    let inside = area_12p >= 0.0 && area_23p >= 0.0 && area_31p >= 0.0;
-
-    // Calculate normalized weights for trilinear interpolation
-    let total_area = area_12p + area_23p + area_31p;
+    let edges = [EdgeFunction::new(v2, v3), EdgeFunction::new(v3, v1), EdgeFunction::new(v1, v2)];
+    let values = edges.map(|edge| edge.evaluate(point));
+    // If the point is right of all edges, we are inside. Assumes clockwise winding of vertices
+    let inside = edges.iter().zip(values).all(|(edge, value)| edge.is_inside(value));
+
+    // Calculate normalized barycentric weights of v1, v2 and v3. The weight of a vertex is the area of
+    // the sub-triangle opposite to it
+    let total_area = values.iter().sum::<f64>();
     if total_area < 1e-16 {  // Escape early if the triangle has no area
         return (false, Float3::zeros())
     }
     let inverse_area = 1.0 / total_area;
-    let weights = Float3::new(area_12p * inverse_area, area_23p * inverse_area, area_31p * inverse_area);
+    let weights = Float3::new(values[0] * inverse_area, values[1] * inverse_area, values[2] * inverse_area);
 
     (inside, weights)
 }
 
 /// Calculate the area of the triangle abc.
-/// 
-/// A positive area means clockwise winding of the triangle and counter-clockwise for negative area
+///
+/// With the y-axis pointing down, as in screen space, a positive area means clockwise winding of the triangle and
+/// counter-clockwise for negative area
 pub fn signed_triangle_area(a: &Float2, b: &Float2, c: &Float2) -> f64 {
-    let ac = c - a;
-    let ab_normal = (b - a).perpendicular();
-    ac.dot(&ab_normal) / 2.0
-}
\ No newline at end of file
+    (b - a).cross(&(c - a)) / 2.0
+}
+
+/// The edge function of the edge from `a` to `b`: twice the signed area of the triangle the edge forms with a point.
+/// With the y-axis pointing down, it is positive right of the edge, which is the inside of clockwise triangles, zero on
+/// the edge and negative left of it.
+///
+/// The function is linear, so it changes by constant steps from pixel to pixel.
+#[derive(Clone, Copy, Debug)]
+pub struct EdgeFunction {
+    /// The end points of the edge in a canonical order, so both directions of an edge evaluate the same expression
+    start: Float2,
+    end: Float2,
+    /// -1 if the end points were swapped for the canonical order, else 1
+    sign: f64,
+    /// Change of the value per pixel along x
+    pub step_x: f64,
+    /// Change of the value per pixel along y
+    pub step_y: f64,
+    /// Whether this is a top edge (horizontal with the triangle below it) or a left edge of a clockwise triangle,
+    /// which own the points exactly on them
+    pub is_top_left: bool,
+}
+
+impl EdgeFunction {
+    pub fn new(a: &Float2, b: &Float2) -> Self {
+        let (start, end, sign) = if (a.x, a.y) <= (b.x, b.y) { (*a, *b, 1.0) } else { (*b, *a, -1.0) };
+        // The steps come from the same expression as `evaluate`, so stepping matches evaluating for both directions
+        let edge = (end - start) * sign;
+        Self {
+            start,
+            end,
+            sign,
+            step_x: -edge.y,
+            step_y: edge.x,
+            is_top_left: (edge.y == 0.0 && edge.x > 0.0) || edge.y < 0.0,
+        }
+    }
+
+    /// The value of the function at the point. The two triangles sharing an edge get exactly opposite values, so
+    /// together with the fill rule, every point on the edge belongs to exactly one of them.
+    pub fn evaluate(&self, point: &Float2) -> f64 {
+        self.sign * (self.end - self.start).cross(&(point - self.start))
+    }
+
+    /// Whether a value of the function counts as inside the triangle, following the top-left fill rule for points
+    /// exactly on the edge
+    pub fn is_inside(&self, value: f64) -> bool {
+        value > 0.0 || (value == 0.0 && self.is_top_left)
+    }
+}
+
+/// Number of fractional bits of fixed-point screen coordinates
+pub const SUBPIXEL_BITS: u32 = 8;
+/// The fixed-point value of one pixel
+pub const SUBPIXEL_SCALE: i64 = 1 << SUBPIXEL_BITS;
+/// Largest magnitude of a screen coordinate in pixels that can be converted to fixed point. It keeps the values of
+/// [`FixedEdgeFunction`] within 63 bits.
+pub const FIXED_POINT_RANGE: f64 = (1 << 21) as f64;
+
+/// A point with fixed-point coordinates in units of 1/[`SUBPIXEL_SCALE`] pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixed2 {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Fixed2 {
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    /// Round the point to the closest subpixel. Returns `None` if a coordinate is outside of [`FIXED_POINT_RANGE`].
+    pub fn from_float(point: &Float2) -> Option<Self> {
+        let in_range = |value: f64| value.abs() <= FIXED_POINT_RANGE;
+        if !in_range(point.x) || !in_range(point.y) {
+            return None;
+        }
+        let scale = SUBPIXEL_SCALE as f64;
+        Some(Self::new((point.x * scale).round() as i64, (point.y * scale).round() as i64))
+    }
+
+    /// The center of the pixel with the given integer coordinates
+    pub fn pixel_center(x: usize, y: usize) -> Self {
+        Self::new(x as i64 * SUBPIXEL_SCALE + SUBPIXEL_SCALE / 2, y as i64 * SUBPIXEL_SCALE + SUBPIXEL_SCALE / 2)
+    }
+
+    pub fn to_float(self) -> Float2 {
+        let scale = SUBPIXEL_SCALE as f64;
+        Float2::new(self.x as f64 / scale, self.y as f64 / scale)
+    }
+}
+
+/// The [`EdgeFunction`] of the edge from `a` to `b` on fixed-point coordinates. Its values are exact integers in units
+/// of 1/[`SUBPIXEL_SCALE`]² square pixels, so stepping them from pixel to pixel gives the same values as evaluating
+/// them directly, and the results do not depend on float rounding.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedEdgeFunction {
+    start: Fixed2,
+    edge: Fixed2,
+    /// Change of the value per pixel along x
+    pub step_x: i64,
+    /// Change of the value per pixel along y
+    pub step_y: i64,
+    /// Whether this is a top edge (horizontal with the triangle below it) or a left edge of a clockwise triangle,
+    /// which own the points exactly on them
+    pub is_top_left: bool,
+}
+
+impl FixedEdgeFunction {
+    pub fn new(a: &Fixed2, b: &Fixed2) -> Self {
+        let edge = Fixed2::new(b.x - a.x, b.y - a.y);
+        Self {
+            start: *a,
+            edge,
+            step_x: -edge.y * SUBPIXEL_SCALE,
+            step_y: edge.x * SUBPIXEL_SCALE,
+            is_top_left: (edge.y == 0 && edge.x > 0) || edge.y < 0,
+        }
+    }
+
+    /// The value of the function at the point
+    pub fn evaluate(&self, point: &Fixed2) -> i64 {
+        self.edge.x * (point.y - self.start.y) - self.edge.y * (point.x - self.start.x)
+    }
+
+    /// Whether a value of the function counts as inside the triangle, following the top-left fill rule for points
+    /// exactly on the edge
+    pub fn is_inside(&self, value: i64) -> bool {
+        value > 0 || (value == 0 && self.is_top_left)
+    }
+}
@@ -1,7 +1,7 @@
-use std::ops::{Add, AddAssign, Div, Mul, Sub};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 /// Float representation of 3D vector
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Float3 {
     pub x: f64,
     pub y: f64,
@@ -19,9 +19,23 @@ impl Float3 {
         Self::new(0.0, 0.0, 0.0)
     }
 
-    /// Dot product between two Float3 references
-    pub fn dot(&self, other: &Self) -> f64 {
-        self.x * other.x + self.y * other.y + self.z * other.z
+    /// Creates a vector with all components set to `value`
+    pub fn splat(value: f64) -> Self {
+        Self::new(value, value, value)
+    }
+
+    /// Cross product between two Float3 references
+    pub fn cross(&self, other: &Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// The first two components
+    pub fn xy(&self) -> Float2 {
+        Float2::new(self.x, self.y)
     }
 
     /// Alias for the x-component. Useful for working with colors.
@@ -40,131 +54,380 @@ impl Float3 {
     }
 }
 
-impl Add<f64> for Float3 {
-    type Output = Float3;
-    fn add(self, rhs: f64) -> Self::Output {
-        Self::Output { x: self.x + rhs, y: self.y + rhs, z: self.z + rhs }
-    }
+
+/// Float representation of 2D vector
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Float2 {
+    pub x: f64,
+    pub y: f64,
 }
 
-impl Add<Float3> for Float3 {
-    type Output = Float3;
-    fn add(self, other: Float3) -> Self::Output {
-        Self::Output { x: self.x + other.x, y: self.y + other.y, z: self.z + other.z }
+impl Float2 {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
     }
-}
 
-impl<'a> Add<&'a Float3> for Float3 {
-    type Output = Float3;
-    fn add(self, other: &'a Float3) -> Self::Output {
-        Self::Output { x: self.x + other.x, y: self.y + other.y, z: self.z + other.z }
+    pub fn zeros() -> Self {
+        Self::new(0.0, 0.0)
     }
-}
 
-impl AddAssign<Float3> for Float3 {
-    fn add_assign(&mut self, other: Float3) {
-        self.x += other.x;
-        self.y += other.y;
-        self.z += other.z;
+    /// Creates a vector with all components set to `value`
+    pub fn splat(value: f64) -> Self {
+        Self::new(value, value)
     }
-}
 
-impl Mul<f64> for Float3 {
-    type Output = Float3;
-    fn mul(self, rhs: f64) -> Self::Output {
-        Self::Output { x: self.x * rhs, y: self.y * rhs, z: self.z * rhs }
+    /// The z-component of the cross product of the vectors extended to 3D
+    pub fn cross(&self, other: &Self) -> f64 {
+        self.x * other.y - self.y * other.x
     }
-}
 
-impl Mul<f64> for &Float3 {
-    type Output = Float3;
-    fn mul(self, rhs: f64) -> Self::Output {
-        *self * rhs
+    // Get vector rotated by 90 degrees clockwise
+    pub fn perpendicular(&self) -> Self {
+        Self { x: self.y, y: -self.x }
     }
 }
 
 
-#[derive(Clone, Debug)]
-pub struct Float2 {
+/// Float representation of 4D vector, e.g. homogeneous coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Float4 {
     pub x: f64,
     pub y: f64,
+    pub z: f64,
+    pub w: f64,
 }
 
-impl Float2 {
-    pub fn new(x: f64, y: f64) -> Self {
-        Self { x, y }
+impl Float4 {
+    /// Creates new 4D vector
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Self {
+        Self { x, y, z, w }
     }
 
+    /// Creates a Zero-vector
     pub fn zeros() -> Self {
-        Self::new(0.0, 0.0)
+        Self::new(0.0, 0.0, 0.0, 0.0)
     }
 
-    pub fn dot(&self, other: &Self) -> f64 {
-        self.x * other.x + self.y * other.y
+    /// Creates a vector with all components set to `value`
+    pub fn splat(value: f64) -> Self {
+        Self::new(value, value, value, value)
     }
 
-    // Get vector rotated by 90 degrees clockwise
-    pub fn perpendicular(&self) -> Self {
-        Self { x: self.y, y: -self.x }
+    /// Homogeneous coordinates of a position (w = 1)
+    pub fn from_point(point: &Float3) -> Self {
+        Self::new(point.x, point.y, point.z, 1.0)
     }
-}
 
-/// Implement addition between Float2 references
-impl<'a, 'b> Add<&'b Float2> for &'a Float2 {
-    type Output = Float2;
+    /// Homogeneous coordinates of a direction (w = 0), which is unaffected by translation
+    pub fn from_direction(direction: &Float3) -> Self {
+        Self::new(direction.x, direction.y, direction.z, 0.0)
+    }
 
-    fn add(self, other: &'b Float2) -> Self::Output {
-        Float2 { x: self.x + other.x, y: self.y + other.y }
+    /// The first three components
+    pub fn xyz(&self) -> Float3 {
+        Float3::new(self.x, self.y, self.z)
     }
 }
 
-/// Implement in-place addition using Float2 reference
-impl<'b> AddAssign<&'b Float2> for Float2 {    
-    fn add_assign(&mut self, other: &'b Float2) {
-        self.x += other.x;
-        self.y += other.y;
-    }
+
+/// Implements the methods shared by all vector types
+macro_rules! impl_vector_methods {
+    ($vector:ident { $($field:ident),+ }) => {
+        impl $vector {
+            /// Dot product between two references
+            pub fn dot(&self, other: &Self) -> f64 {
+                0.0 $(+ self.$field * other.$field)+
+            }
+
+            /// Squared Euclidean length of the vector
+            pub fn length_squared(&self) -> f64 {
+                self.dot(self)
+            }
+
+            /// Euclidean length of the vector
+            pub fn length(&self) -> f64 {
+                self.length_squared().sqrt()
+            }
+
+            /// Vector with the same direction and unit length
+            pub fn normalize(&self) -> Self {
+                self / self.length()
+            }
+
+            /// Component-wise minimum
+            pub fn min(&self, other: &Self) -> Self {
+                Self { $($field: self.$field.min(other.$field)),+ }
+            }
+
+            /// Component-wise maximum
+            pub fn max(&self, other: &Self) -> Self {
+                Self { $($field: self.$field.max(other.$field)),+ }
+            }
+
+            /// Clamp each component to the range given by the components of `min` and `max`
+            pub fn clamp(&self, min: &Self, max: &Self) -> Self {
+                Self { $($field: self.$field.clamp(min.$field, max.$field)),+ }
+            }
+
+            /// Linear interpolation from `self` (t = 0) to `other` (t = 1)
+            pub fn lerp(&self, other: &Self, t: f64) -> Self {
+                self + (other - self) * t
+            }
+
+            /// Reflect the vector on the plane with the (unit) `normal`
+            pub fn reflect(&self, normal: &Self) -> Self {
+                self - normal * (2.0 * self.dot(normal))
+            }
+
+            /// Whether all components differ by at most `tolerance`. Useful for comparing results in tests.
+            pub fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+                true $(&& (self.$field - other.$field).abs() <= tolerance)+
+            }
+        }
+    };
 }
 
-/// Implement in-place addition using Float2 (consumes)
-impl AddAssign<Float2> for Float2 {
-    fn add_assign(&mut self, other: Float2) {
-        self.x += other.x;
-        self.y += other.y;
-    }
+/// Implements a component-wise binary operator between all combinations of vector values and references,
+/// and between a vector and a scalar
+macro_rules! impl_binary_operator {
+    ($vector:ident { $($field:ident),+ }, $trait:ident, $method:ident, $op:tt) => {
+        impl $trait<$vector> for $vector {
+            type Output = $vector;
+            fn $method(self, rhs: $vector) -> Self::Output {
+                $vector { $($field: self.$field $op rhs.$field),+ }
+            }
+        }
+
+        impl $trait<&$vector> for $vector {
+            type Output = $vector;
+            fn $method(self, rhs: &$vector) -> Self::Output {
+                $vector { $($field: self.$field $op rhs.$field),+ }
+            }
+        }
+
+        impl $trait<$vector> for &$vector {
+            type Output = $vector;
+            fn $method(self, rhs: $vector) -> Self::Output {
+                $vector { $($field: self.$field $op rhs.$field),+ }
+            }
+        }
+
+        impl $trait<&$vector> for &$vector {
+            type Output = $vector;
+            fn $method(self, rhs: &$vector) -> Self::Output {
+                $vector { $($field: self.$field $op rhs.$field),+ }
+            }
+        }
+
+        impl $trait<f64> for $vector {
+            type Output = $vector;
+            fn $method(self, rhs: f64) -> Self::Output {
+                $vector { $($field: self.$field $op rhs),+ }
+            }
+        }
+
+        impl $trait<f64> for &$vector {
+            type Output = $vector;
+            fn $method(self, rhs: f64) -> Self::Output {
+                $vector { $($field: self.$field $op rhs),+ }
+            }
+        }
+    };
 }
 
+/// Implements a component-wise in-place operator with a vector value, reference or scalar
+macro_rules! impl_assign_operator {
+    ($vector:ident { $($field:ident),+ }, $trait:ident, $method:ident, $op:tt) => {
+        impl $trait<$vector> for $vector {
+            fn $method(&mut self, rhs: $vector) {
+                $(self.$field $op rhs.$field;)+
+            }
+        }
 
-/// Implement subtraction between Float2 reference and f64
-impl<'a, 'b> Sub<&'b Float2> for &'a Float2 {
-    type Output = Float2;
+        impl $trait<&$vector> for $vector {
+            fn $method(&mut self, rhs: &$vector) {
+                $(self.$field $op rhs.$field;)+
+            }
+        }
 
-    fn sub(self, other: &'b Float2) -> Float2 {
-        Float2 { x: self.x - other.x, y: self.y - other.y }
-    }
+        impl $trait<f64> for $vector {
+            fn $method(&mut self, rhs: f64) {
+                $(self.$field $op rhs;)+
+            }
+        }
+    };
 }
 
-/// Implement multiplication between Float2 reference and f64
-impl<'a> Mul<f64> for &'a Float2 {
-    type Output = Float2;
+/// Implements the full set of arithmetic operators for a vector type.
+/// Multiplication and division between two vectors are component-wise.
+macro_rules! impl_vector_operators {
+    ($vector:ident { $($field:ident),+ }) => {
+        impl_binary_operator!($vector { $($field),+ }, Add, add, +);
+        impl_binary_operator!($vector { $($field),+ }, Sub, sub, -);
+        impl_binary_operator!($vector { $($field),+ }, Mul, mul, *);
+        impl_binary_operator!($vector { $($field),+ }, Div, div, /);
 
-    fn mul(self, rhs: f64) -> Self::Output {
-        Float2 {x: self.x * rhs, y: self.y * rhs}
-    }
+        impl_assign_operator!($vector { $($field),+ }, AddAssign, add_assign, +=);
+        impl_assign_operator!($vector { $($field),+ }, SubAssign, sub_assign, -=);
+        impl_assign_operator!($vector { $($field),+ }, MulAssign, mul_assign, *=);
+        impl_assign_operator!($vector { $($field),+ }, DivAssign, div_assign, /=);
+
+        impl Neg for $vector {
+            type Output = $vector;
+            fn neg(self) -> Self::Output {
+                $vector { $($field: -self.$field),+ }
+            }
+        }
+
+        impl Neg for &$vector {
+            type Output = $vector;
+            fn neg(self) -> Self::Output {
+                $vector { $($field: -self.$field),+ }
+            }
+        }
+
+        /// Scaling with the scalar on the left-hand side
+        impl Mul<$vector> for f64 {
+            type Output = $vector;
+            fn mul(self, rhs: $vector) -> Self::Output {
+                rhs * self
+            }
+        }
+
+        impl Mul<&$vector> for f64 {
+            type Output = $vector;
+            fn mul(self, rhs: &$vector) -> Self::Output {
+                rhs * self
+            }
+        }
+    };
 }
 
-/// Implement multiplication between a Float2 (consumes) and an f64
-impl Mul<f64> for Float2 {
-    type Output = Float2;
+impl_vector_methods!(Float2 { x, y });
+impl_vector_methods!(Float3 { x, y, z });
+impl_vector_methods!(Float4 { x, y, z, w });
+
+impl_vector_operators!(Float2 { x, y });
+impl_vector_operators!(Float3 { x, y, z });
+impl_vector_operators!(Float4 { x, y, z, w });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn mul(self, rhs: f64) -> Self::Output {
-        Float2 {x: self.x * rhs, y: self.y * rhs}
+    /// Check a binary operator and its assigning form for every combination of values and references
+    macro_rules! assert_operator {
+        ($a:expr, $op:tt, $op_assign:tt, $b:expr, $expected:expr) => {{
+            let (a, b, expected) = ($a, $b, $expected);
+            assert_eq!(a $op b, expected);
+            assert_eq!(a $op &b, expected);
+            assert_eq!(&a $op b, expected);
+            assert_eq!(&a $op &b, expected);
+            let mut c = a;
+            c $op_assign b;
+            assert_eq!(c, expected);
+            let mut c = a;
+            c $op_assign &b;
+            assert_eq!(c, expected);
+        }};
     }
-}
 
-impl<'a> Div<f64> for &'a Float2 {
-    type Output = Float2;
-    fn div(self, rhs: f64) -> Self::Output {
-        Self::Output { x: self.x / rhs, y: self.y / rhs }
+    /// Check a binary operator with a scalar on the right-hand side and its assigning form
+    macro_rules! assert_scalar_operator {
+        ($a:expr, $op:tt, $op_assign:tt, $s:expr, $expected:expr) => {{
+            let (a, s, expected) = ($a, $s, $expected);
+            assert_eq!(a $op s, expected);
+            assert_eq!(&a $op s, expected);
+            let mut c = a;
+            c $op_assign s;
+            assert_eq!(c, expected);
+        }};
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn float3_operators_are_component_wise() {
+        let a = Float3::new(1.5, -2.0, 4.0);
+        let b = Float3::new(0.5, 4.0, -2.0);
+        assert_operator!(a, +, +=, b, Float3::new(2.0, 2.0, 2.0));
+        assert_operator!(a, -, -=, b, Float3::new(1.0, -6.0, 6.0));
+        assert_operator!(a, *, *=, b, Float3::new(0.75, -8.0, -8.0));
+        assert_operator!(a, /, /=, b, Float3::new(3.0, -0.5, -2.0));
+
+        assert_scalar_operator!(a, +, +=, 2.0, Float3::new(3.5, 0.0, 6.0));
+        assert_scalar_operator!(a, -, -=, 2.0, Float3::new(-0.5, -4.0, 2.0));
+        assert_scalar_operator!(a, *, *=, 2.0, Float3::new(3.0, -4.0, 8.0));
+        assert_scalar_operator!(a, /, /=, 2.0, Float3::new(0.75, -1.0, 2.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(2.0 * &a, a * 2.0);
+
+        assert_eq!(-a, Float3::new(-1.5, 2.0, -4.0));
+        assert_eq!(-&a, -a);
+    }
+
+    #[test]
+    fn float2_operators_are_component_wise() {
+        let a = Float2::new(1.5, -2.0);
+        let b = Float2::new(0.5, 4.0);
+        assert_operator!(a, +, +=, b, Float2::new(2.0, 2.0));
+        assert_operator!(a, -, -=, b, Float2::new(1.0, -6.0));
+        assert_operator!(a, *, *=, b, Float2::new(0.75, -8.0));
+        assert_operator!(a, /, /=, b, Float2::new(3.0, -0.5));
+
+        assert_scalar_operator!(a, +, +=, 2.0, Float2::new(3.5, 0.0));
+        assert_scalar_operator!(a, -, -=, 2.0, Float2::new(-0.5, -4.0));
+        assert_scalar_operator!(a, *, *=, 2.0, Float2::new(3.0, -4.0));
+        assert_scalar_operator!(a, /, /=, 2.0, Float2::new(0.75, -1.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(2.0 * &a, a * 2.0);
+
+        assert_eq!(-a, Float2::new(-1.5, 2.0));
+        assert_eq!(-&a, -a);
+    }
+
+    #[test]
+    fn cross_products_follow_the_right_hand_rule() {
+        let (x, y, z) = (Float3::new(1.0, 0.0, 0.0), Float3::new(0.0, 1.0, 0.0), Float3::new(0.0, 0.0, 1.0));
+        assert_eq!(x.cross(&y), z);
+        assert_eq!(y.cross(&z), x);
+        assert_eq!(z.cross(&x), y);
+
+        let a = Float3::new(1.5, -2.0, 4.0);
+        let b = Float3::new(0.5, 4.0, -2.0);
+        assert_eq!(a.cross(&b), -b.cross(&a));
+        assert_eq!(a.cross(&b).dot(&a), 0.0);
+        assert_eq!(a.cross(&b).dot(&b), 0.0);
+
+        // The 2D cross product is the z-component of the 3D one
+        let cross_3d = Float3::new(1.5, -2.0, 0.0).cross(&Float3::new(0.5, 4.0, 0.0));
+        assert_eq!(Float2::new(1.5, -2.0).cross(&Float2::new(0.5, 4.0)), cross_3d.z);
+        assert_eq!(Float2::new(1.0, 0.0).cross(&Float2::new(0.0, 1.0)), 1.0);
+    }
+
+    #[test]
+    fn vector_utilities() {
+        let a = Float3::new(1.5, -2.0, 4.0);
+        let b = Float3::new(0.5, 4.0, -2.0);
+
+        assert_eq!(Float3::new(3.0, 4.0, 12.0).length(), 13.0);
+        assert!(Float3::new(3.0, 4.0, 12.0).normalize().approx_eq(&(Float3::new(3.0, 4.0, 12.0) / 13.0), 1e-15));
+        assert_eq!(Float2::new(-3.0, 4.0).normalize(), Float2::new(-0.6, 0.8));
+
+        // Reflecting on a plane flips the component along the normal
+        assert_eq!(Float3::new(1.0, -1.0, 2.0).reflect(&Float3::new(0.0, 1.0, 0.0)), Float3::new(1.0, 1.0, 2.0));
+        assert_eq!(Float2::new(1.0, -1.0).reflect(&Float2::new(-1.0, 0.0)), Float2::new(-1.0, -1.0));
+
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(a.lerp(&b, 0.25), Float3::new(1.25, -0.5, 2.5));
+
+        assert_eq!(a.min(&b), Float3::new(0.5, -2.0, -2.0));
+        assert_eq!(a.max(&b), Float3::new(1.5, 4.0, 4.0));
+        assert_eq!(a.clamp(&Float3::zeros(), &Float3::splat(1.0)), Float3::new(1.0, 0.0, 1.0));
+        assert_eq!(Float2::new(1.5, -2.0).clamp(&Float2::splat(-1.0), &Float2::splat(1.0)), Float2::new(1.0, -1.0));
+
+        // The tolerance is inclusive and applies to each component separately
+        assert!(a.approx_eq(&(a + Float3::new(0.5, -0.5, 0.0)), 0.5));
+        assert!(!a.approx_eq(&(a + Float3::new(0.0, 0.0, 0.75)), 0.5));
+        assert!(!Float2::new(0.0, 0.0).approx_eq(&Float2::new(0.0, f64::NAN), 1.0));
+    }
+}